@@ -0,0 +1,74 @@
+use std::io::Read;
+use std::process::exit;
+
+use token_editor::cli::{self, Output};
+use token_editor::{language, Formatter, Rustfmt, DEFAULT_MAX_WIDTH};
+
+const USAGE: &str =
+    "usage: token-editor-cli [--rustfmt] [--width N] <typed|formatted|report> [FILE]
+
+  typed      print the minimal typed text of a formatted file
+  formatted  print the formatted (displayed) text of a typed file
+  report     print the typed and virtual whitespace of every token of a formatted file
+
+Reads from stdin if no file is given. The formatter is chosen by the file extension (Rust, JSON,
+TOML or C-like languages), stdin is formatted as Rust. Rust is formatted with prettyplease, or
+with rustfmt (found on PATH) if --rustfmt is given. Lines are formatted to a maximum width of
+100 chars, unless set by --width.";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut rustfmt = false;
+    let mut max_width = DEFAULT_MAX_WIDTH;
+    while args.first().is_some_and(|a| a.starts_with("--")) {
+        match args.remove(0).as_str() {
+            "--rustfmt" => rustfmt = true,
+            "--width" if !args.is_empty() => {
+                max_width = args.remove(0).parse().unwrap_or_else(|e| {
+                    eprintln!("invalid width: {}", e);
+                    exit(2);
+                })
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+    let (output, path) = match args.as_slice() {
+        [output] => (output, None),
+        [output, path] => (output, Some(path)),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    let output: Output = output.parse().unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        exit(2);
+    });
+
+    let language = language::for_path(path.map_or("", |p| p.as_str()));
+    let formatter: Box<dyn Formatter> = if rustfmt && language == language::RUST {
+        Box::new(Rustfmt::default())
+    } else {
+        (language.formatter)()
+    };
+
+    let input = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s).map(|_| s)
+        }
+    };
+    let input = input.unwrap_or_else(|e| {
+        eprintln!("error reading input: {}", e);
+        exit(1);
+    });
+
+    print!(
+        "{}",
+        cli::convert(&input, output, formatter.as_ref(), max_width)
+    );
+}
@@ -0,0 +1,25 @@
+use std::process::exit;
+
+use token_editor::{language, Formatter, Rustfmt};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, rustfmt) = match args.as_slice() {
+        [path] => (path, false),
+        [flag, path] if flag == "--rustfmt" => (path, true),
+        _ => {
+            eprintln!("usage: token-editor-tui [--rustfmt] FILE");
+            exit(2);
+        }
+    };
+    let language = language::for_path(path);
+    let formatter: Box<dyn Formatter> = if rustfmt && language == language::RUST {
+        Box::new(Rustfmt::default())
+    } else {
+        (language.formatter)()
+    };
+    if let Err(e) = token_editor::tui::run(path.into(), formatter) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,6 @@
+// Entry point of the Web Worker that formats the code for the web frontend
+use gloo::worker::Registrable;
+
+fn main() {
+    token_editor::FormatWorker::registrar().register();
+}
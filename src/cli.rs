@@ -0,0 +1,99 @@
+// Headless conversion between the typed and the formatted form of Rust code, used by the
+// `token-editor-cli` binary.
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::content::{Content, GetString};
+use crate::format::Formatter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    Typed,     // the minimal typed text of formatted input
+    Formatted, // the displayed text of typed input
+    Report,    // typed and virtual whitespace of every token of formatted input
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "typed" => Ok(Output::Typed),
+            "formatted" => Ok(Output::Formatted),
+            "report" => Ok(Output::Report),
+            other => Err(format!("unknown output '{}'", other)),
+        }
+    }
+}
+
+pub fn convert(input: &str, output: Output, formatter: &dyn Formatter, max_width: usize) -> String {
+    match output {
+        Output::Typed => Content::from_formatted(input, formatter, max_width).typed_string(),
+        Output::Formatted => {
+            let mut content = Content::from_string(input);
+            content.update_virtual_whitespace(formatter, max_width);
+            content.get_string()
+        }
+        Output::Report => report(&Content::from_formatted(input, formatter, max_width)),
+    }
+}
+
+// One line per token: position (1-based), token, typed whitespace and virtual whitespace before it
+fn report(content: &Content) -> String {
+    let tokens = content.token_whitespace();
+    let mut s = String::new();
+    for t in &tokens {
+        writeln!(
+            s,
+            "{}:{}\t{:?}\ttyped {:?}\tvirtual {:?}",
+            t.pos.0 + 1,
+            t.pos.1 + 1,
+            t.token,
+            t.typed,
+            t.virtual_whitespace
+        )
+        .unwrap();
+    }
+    let typed: usize = tokens.iter().map(|t| t.typed.len()).sum();
+    let virtual_whitespace: usize = tokens.iter().map(|t| t.virtual_whitespace.len()).sum();
+    writeln!(
+        s,
+        "{} tokens, {} typed and {} virtual whitespace chars",
+        tokens.len(),
+        typed,
+        virtual_whitespace
+    )
+    .unwrap();
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{Prettyplease, DEFAULT_MAX_WIDTH};
+
+    #[test]
+    fn test_convert() {
+        let formatted = "pub fn a() -> u8 {\n    1\n}\n";
+        let typed = convert(formatted, Output::Typed, &Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(typed, "pub fn a()->u8{1}");
+        assert_eq!(
+            convert(&typed, Output::Formatted, &Prettyplease, DEFAULT_MAX_WIDTH),
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let report = convert(
+            "fn a() {}\n",
+            Output::Report,
+            &Prettyplease,
+            DEFAULT_MAX_WIDTH,
+        );
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[1], "1:4\t\"a\"\ttyped \" \"\tvirtual \"\"");
+        assert_eq!(lines[4], "1:8\t\"{\"\ttyped \"\"\tvirtual \" \"");
+        assert_eq!(lines[6], "6 tokens, 1 typed and 1 virtual whitespace chars");
+    }
+}
@@ -1,11 +1,21 @@
+use std::collections::HashMap;
 use std::string::ToString;
 
+use crate::diff;
+use crate::format::Formatter;
+use crate::history::{Edit, History};
+use crate::lexer::{self, Highlight, Syntax, TokenKind};
+use crate::rope::{Rope, Summarize};
+
 #[derive(Clone, Debug)]
 pub struct Content {
-    elmts: Vec<Elmt>,
+    elmts: Rope<Elmt>,
     cursor: (usize, usize), // first element is the index of the selected whitespace element.
     // the second element is the selection index within that whitespace element
     spacial_cursor: (usize, usize),
+    anchor: Option<(usize, usize)>, // the other end of the selection, if any
+    history: History,
+    syntax: Syntax, // of the formatter used for the last update of the virtual whitespace
 }
 
 #[derive(Clone, Debug)]
@@ -27,21 +37,80 @@ pub enum WhitespaceChar {
     Newline,
 }
 
-type CursorPos = ((usize, usize), (usize, usize));
+// Result of aligning the typed elements with a formatted text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alignment {
+    pub positions: Vec<Option<usize>>, // char index in the formatted text for every element
+    pub inserted: Vec<usize>,          // char indices of formatted chars that weren't typed
+    pub deleted: Vec<usize>,           // indices of elements that are missing in the formatted text
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CopyMode {
+    Visible, // the text as displayed, including virtual whitespace
+    Typed,   // only the typed text
+}
+
+pub type CursorPos = ((usize, usize), (usize, usize));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanKind {
+    Token, // the chars of the elements
+    TypedWhitespace,
+    VirtualWhitespace,
+}
+
+// A piece of a displayed line. A whitespace span is part of the whitespace of element `elmt`, a
+// token span consists of the chars of the elements `elmt`, `elmt + 1`, ...
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub text: String, // ends with a newline if the span ends the line
+    pub kind: SpanKind,
+    pub elmt: usize,
+    pub highlight: Option<Highlight>, // of the token chars
+}
+
+// Formatted output of top-level items, so that items that didn't change since the last update
+// don't need to be formatted again. A cache must only be used with one formatter.
+#[derive(Clone, Debug, Default)]
+pub struct FormatCache {
+    max_width: usize,
+    items: HashMap<String, Option<String>>, // formatted output by the typed text of the item
+}
+
+// Whitespace displayed in front of a token of the typed text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenWhitespace {
+    pub token: String,
+    pub pos: (usize, usize), // visual (line, column) of the token
+    pub typed: String,
+    pub virtual_whitespace: String, // displayed after the typed whitespace
+}
 
 impl WhitespaceChar {
     fn is_newline(&self) -> bool {
         matches!(self, WhitespaceChar::Newline)
     }
+
+    fn as_char(&self) -> char {
+        match self {
+            WhitespaceChar::Space => ' ',
+            WhitespaceChar::Newline => '\n',
+        }
+    }
 }
 
 pub trait GetString {
-    fn get_string(&self) -> String; // TODO: add option for visible whitespace
+    fn get_string(&self) -> String; // see `visible_whitespace` for displaying the whitespace
+}
+
+// Replaces spaces and newlines with visible characters, keeping the line breaks
+pub fn visible_whitespace(text: &str) -> String {
+    text.replace(' ', "·").replace('\n', "¶\n")
 }
 
 impl GetString for WhitespaceChar {
     fn get_string(&self) -> String {
-        // visible whitespace characters · ¶
         match self {
             WhitespaceChar::Space => " ".to_string(),
             WhitespaceChar::Newline => "\n".to_string(),
@@ -83,6 +152,69 @@ impl GetString for Elmt {
     }
 }
 
+// Summary of a sequence of elements, kept up to date by the rope that stores them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ElmtSummary {
+    typed: usize, // length of the typed text, including the element chars
+    lines: usize, // number of displayed newlines
+    col: usize,   // number of displayed chars after the last newline
+}
+
+impl ElmtSummary {
+    // visual position after the summarized elements
+    fn end(&self) -> (usize, usize) {
+        (self.lines, self.col)
+    }
+}
+
+impl std::ops::Add for ElmtSummary {
+    type Output = ElmtSummary;
+
+    fn add(self, other: ElmtSummary) -> ElmtSummary {
+        ElmtSummary {
+            typed: self.typed + other.typed,
+            lines: self.lines + other.lines,
+            col: if other.lines == 0 {
+                self.col + other.col
+            } else {
+                other.col
+            },
+        }
+    }
+}
+
+impl Summarize for Elmt {
+    type Summary = ElmtSummary;
+
+    fn summarize(&self) -> ElmtSummary {
+        // the displayed whitespace, see `Whitespace::get_string`
+        let ws = &self.whitespace;
+        let typed_newlines = ws.typed.iter().filter(|x| x.is_newline()).count();
+        let typed_spaces = ws
+            .typed
+            .iter()
+            .rev()
+            .take_while(|x| !x.is_newline())
+            .count();
+        let (lines, col) = if typed_newlines < ws.virtual_newlines {
+            (ws.virtual_newlines, ws.virtual_spaces)
+        } else {
+            (
+                typed_newlines,
+                std::cmp::max(
+                    typed_spaces,
+                    ws.virtual_spaces * usize::from(typed_newlines == ws.virtual_newlines),
+                ),
+            )
+        };
+        ElmtSummary {
+            typed: ws.typed.len() + 1,
+            lines,
+            col: col + usize::from(self.character != '\0'),
+        }
+    }
+}
+
 impl GetString for Content {
     fn get_string(&self) -> String {
         self.elmts.iter().map(|x| x.get_string()).collect()
@@ -123,46 +255,32 @@ impl Content {
         });
 
         Content {
-            elmts,
+            elmts: elmts.into(),
             cursor: (0, 0),
             spacial_cursor: (0, 0),
+            anchor: None,
+            history: History::default(),
+            syntax: Syntax::default(),
         }
     }
 
-    pub fn update_virtual_whitespace_2(&mut self, formatted_input: &str) {
-        let mut chars = formatted_input.chars().peekable();
+    // Identifies the typed text, e.g. to check for unsaved changes. Edits change the version and
+    // undoing them restores the earlier version, see `History::version`.
+    pub fn version(&self) -> u64 {
+        self.history.version()
+    }
 
-        for elmt in &mut self.elmts {
-            let mut virtual_newlines = 0;
-            let mut virtual_spaces = 0;
-            while let Some(vc) = chars.peek() {
-                match vc {
-                    '\n' => {
-                        virtual_newlines += 1;
-                        virtual_spaces = 0;
-                        chars.next();
-                    }
-                    ' ' => {
-                        virtual_spaces += 1;
-                        chars.next();
-                    }
-                    x if x == &elmt.character => {
-                        chars.next();
-                        break;
-                    }
-                    ',' => {
-                        println!("Ignoring comma");
-                        chars.next();
-                    }
-                    _ => {
-                        break;
-                    }
-                }
-            }
+    // the text without virtual whitespace
+    pub fn typed_string(&self) -> String {
+        let last = self.elmts.len() - 1;
+        self.typed_text((0, 0), (last, self.elmts[last].whitespace.typed.len()))
+    }
 
-            elmt.whitespace.virtual_newlines = virtual_newlines;
-            elmt.whitespace.virtual_spaces = virtual_spaces;
-        }
+    pub fn update_virtual_whitespace_2(&mut self, formatted_input: &str) -> Alignment {
+        let len = self.elmts.len();
+        self.elmts.with_slice(0..len, |elmts| {
+            align_virtual_whitespace(elmts, formatted_input)
+        })
     }
 
     pub fn from_strings(typed: &str, visible: &str) -> Content {
@@ -171,47 +289,221 @@ impl Content {
         content
     }
 
-    pub fn cursor_pos(&self) -> CursorPos {
-        let s: String = self
-            .elmts
-            .iter()
-            .take(self.cursor.0)
-            .map(|x| x.get_string())
-            .collect();
-        let mut line = s.chars().filter(|x| x == &'\n').count();
-        let mut col = s.chars().rev().take_while(|x| x != &'\n').count();
+    // Imports already formatted code: all whitespace the formatter reproduces becomes virtual, only
+    // whitespace that separates tokens (e.g. in `pub fn`) stays typed. Whitespace the formatter
+    // doesn't reproduce (e.g. blank lines) is typed, so the displayed text equals the input, except
+    // that `\r` is removed and other whitespace between tokens (e.g. a tab) becomes a single space.
+    pub fn from_formatted(input: &str, formatter: &dyn Formatter, max_width: usize) -> Content {
+        let syntax = formatter.syntax();
+        let original = split_whitespace(input, &syntax);
+        let mut content = Content::from_string(&lexer::minimize_whitespace(input, &syntax));
+        let same_chars = original.len() == content.elmts.len()
+            && original
+                .iter()
+                .zip(&content.elmts)
+                .all(|((c, _), e)| *c == e.character);
+        if !same_chars {
+            return Content::from_string(input);
+        }
 
-        let virtual_spaces = self.elmts[self.cursor.0].whitespace.virtual_spaces;
-        let virtual_newlines = self.elmts[self.cursor.0].whitespace.virtual_newlines;
-        let virtual_end = (
-            line + virtual_newlines,
-            if virtual_newlines == 0 { col } else { 0 } + virtual_spaces,
-        );
+        let reproduced = |e: &Elmt, typed: &[WhitespaceChar]| {
+            e.whitespace.get_string() == typed.iter().map(|x| x.as_char()).collect::<String>()
+        };
 
-        let typed = &self.elmts[self.cursor.0].whitespace.typed;
-        for wc in typed.iter().take(self.cursor.1) {
-            match wc {
-                WhitespaceChar::Space => col += 1,
-                WhitespaceChar::Newline => {
-                    col = 0;
+        // first try typing only the newlines that aren't reproduced, the indentation is usually
+        // virtual then (e.g. for comments on their own line)
+        let len = content.elmts.len();
+        content.update_virtual_whitespace(formatter, max_width);
+        let mut original_iter = original.iter();
+        content.elmts.update_range(0..len, |e| {
+            let (_, typed) = original_iter.next().unwrap();
+            if !reproduced(e, typed) {
+                let indent = typed.iter().rev().take_while(|x| !x.is_newline()).count();
+                let newlines = typed.len() - if indent < typed.len() { indent } else { 0 };
+                e.whitespace.typed = typed[..newlines].to_vec();
+            }
+        });
+
+        content.update_virtual_whitespace(formatter, max_width);
+        let mut original_iter = original.into_iter();
+        content.elmts.update_range(0..len, |e| {
+            let (_, typed) = original_iter.next().unwrap();
+            if !reproduced(e, &typed) {
+                e.whitespace = Whitespace {
+                    typed,
+                    virtual_newlines: 0,
+                    virtual_spaces: 0,
+                };
+            }
+        });
+        content.spacial_cursor = content.cursor_pos().0;
+        content
+    }
+
+    // The displayed lines, split into spans of token chars, typed and virtual whitespace. The
+    // concatenated text of all spans is the displayed text. Token spans are split where the
+    // syntax highlighting changes.
+    pub fn lines(&self) -> Vec<Vec<Span>> {
+        let highlights = self.highlights();
+        let mut lines: Vec<Vec<Span>> = vec![vec![]];
+        let mut push = |text: &str, kind: SpanKind, elmt: usize| {
+            let highlight = highlights[elmt].filter(|_| kind == SpanKind::Token);
+            for part in text.split_inclusive('\n') {
+                let line = lines.last_mut().unwrap();
+                match line.last_mut() {
+                    Some(last)
+                        if kind == SpanKind::Token
+                            && last.kind == kind
+                            && last.highlight == highlight =>
+                    {
+                        last.text.push_str(part)
+                    }
+                    _ => line.push(Span {
+                        text: part.to_string(),
+                        kind,
+                        elmt,
+                        highlight,
+                    }),
+                }
+                if part.ends_with('\n') {
+                    lines.push(vec![]);
+                }
+            }
+        };
+        for (i, e) in self.elmts.iter().enumerate() {
+            let typed: String = e.whitespace.typed.iter().map(|x| x.as_char()).collect();
+            push(&typed, SpanKind::TypedWhitespace, i);
+            push(
+                &e.whitespace.get_string()[typed.len()..],
+                SpanKind::VirtualWhitespace,
+                i,
+            );
+            if e.character != '\0' {
+                push(e.character.encode_utf8(&mut [0; 4]), SpanKind::Token, i);
+            }
+        }
+        lines
+    }
+
+    // The syntax highlighting of every element char, from the lexer over the typed text
+    fn highlights(&self) -> Vec<Option<Highlight>> {
+        let mut chars = vec![];
+        let mut char_idx = vec![]; // index of the element char in `chars`
+        for e in &self.elmts {
+            chars.extend(e.whitespace.typed.iter().map(|x| x.as_char()));
+            char_idx.push(chars.len());
+            if e.character != '\0' {
+                chars.push(e.character);
+            }
+        }
+        let highlights = lexer::highlight(&chars, &self.syntax);
+        char_idx
+            .into_iter()
+            .map(|i| highlights.get(i).copied().flatten())
+            .collect()
+    }
+
+    // The whitespace in front of every token, excluding whitespace in literals and comments
+    pub fn token_whitespace(&self) -> Vec<TokenWhitespace> {
+        let mut chars = vec![];
+        let mut elmt_idx = vec![]; // element of every typed char that isn't whitespace
+        let mut positions = vec![];
+        let (mut line, mut col) = (0, 0);
+        for (i, e) in self.elmts.iter().enumerate() {
+            for c in e.whitespace.get_string().chars() {
+                if c == '\n' {
                     line += 1;
+                    col = 0;
+                } else {
+                    col += 1;
                 }
             }
+            positions.push((line, col));
+            col += 1;
+
+            chars.extend(e.whitespace.typed.iter().map(|x| x.as_char()));
+            elmt_idx.extend(e.whitespace.typed.iter().map(|_| None));
+            if e.character != '\0' {
+                chars.push(e.character);
+                elmt_idx.push(Some(i));
+            }
         }
 
-        let start = (line, col);
-        let end = if self.cursor.1
-            == self.elmts[self.cursor.0]
-                .whitespace
-                .get_num_cursor_positions()
-                - 1
-        {
-            std::cmp::max(start, virtual_end)
+        lexer::tokenize(&chars, &self.syntax)
+            .into_iter()
+            .filter_map(|token| {
+                let i = elmt_idx[token.start]?;
+                let whitespace = &self.elmts[i].whitespace;
+                let typed: String = whitespace.typed.iter().map(|x| x.as_char()).collect();
+                let displayed = whitespace.get_string();
+                Some(TokenWhitespace {
+                    token: chars[token.start..token.end].iter().collect(),
+                    pos: positions[i],
+                    virtual_whitespace: displayed[typed.len()..].to_string(),
+                    typed,
+                })
+            })
+            .collect()
+    }
+
+    pub fn cursor_pos(&self) -> CursorPos {
+        self.area(self.cursor)
+    }
+
+    // number of elements, including the terminating '\0'
+    pub fn num_elmts(&self) -> usize {
+        self.elmts.len()
+    }
+
+    // Visual (line, column) of the character of element `elmt`. The element positions are kept in
+    // the rope, so this takes O(log n).
+    pub fn visual_pos(&self, elmt: usize) -> (usize, usize) {
+        let (line, col) = self.elmts.summary_before(elmt + 1).end();
+        if self.elmts[elmt].character == '\0' {
+            (line, col)
         } else {
-            start
-        };
+            (line, col - 1)
+        }
+    }
 
-        (start, end)
+    // The element whose character is displayed at (line, column), if any
+    pub fn elmt_at(&self, line: usize, col: usize) -> Option<usize> {
+        let i = self.elmts.search(|s| s.end() > (line, col));
+        (i < self.elmts.len() && self.visual_pos(i) == (line, col)).then_some(i)
+    }
+
+    // visual start and end of the given cursor position, including virtual whitespace
+    fn area(&self, pos: (usize, usize)) -> CursorPos {
+        let start = self.elmts.summary_before(pos.0).end();
+        self.elmts[pos.0].whitespace.area(start, pos.1)
+    }
+
+    // Moves the cursor to the position displayed at `target` (line, column). If `target` is
+    // inside virtual whitespace, the cursor is placed in the whitespace element it belongs to.
+    pub fn cursor_to(&mut self, target: (usize, usize)) {
+        // the last element whose whitespace starts at or before the target
+        let i = std::cmp::min(
+            self.elmts.search(|s| s.end() > target),
+            self.elmts.len() - 1,
+        );
+        let whitespace = &self.elmts[i].whitespace;
+        let start = self.elmts.summary_before(i).end();
+        let mut found = ((i, 0), whitespace.area(start, 0));
+        for j in 1..whitespace.get_num_cursor_positions() {
+            let area = whitespace.area(start, j);
+            if area.0 > target {
+                break;
+            }
+            found = ((i, j), area);
+        }
+
+        let (pos, area) = found;
+        self.cursor = pos;
+        self.spacial_cursor = if (area.0).0 == target.0 {
+            area.0
+        } else {
+            area.1
+        };
     }
 
     pub fn cursor_pos_2(&self) -> (CursorPos, (usize, usize)) {
@@ -224,6 +516,208 @@ impl Content {
         (cursor_pos, small)
     }
 
+    // Starts a selection at the cursor if `selecting` is set and there's no selection yet, removes
+    // the selection otherwise. Cursor movements extend an active selection.
+    pub fn set_selecting(&mut self, selecting: bool) {
+        if !selecting {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some(self.cursor);
+        }
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some((0, 0));
+        let last = self.elmts.len() - 1;
+        self.cursor = (
+            last,
+            self.elmts[last].whitespace.get_num_cursor_positions() - 1,
+        );
+        self.spacial_cursor = self.cursor_pos().0;
+    }
+
+    // start and end of the selection (ordered), if it isn't empty
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor?;
+        match anchor.cmp(&self.cursor) {
+            std::cmp::Ordering::Less => Some((anchor, self.cursor)),
+            std::cmp::Ordering::Greater => Some((self.cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // Visual start and end of the selection. Virtual whitespace before the first and after the
+    // last selected character isn't part of the selection.
+    pub fn selection_range(&self) -> Option<CursorPos> {
+        let (start, end) = self.selection()?;
+        Some((self.area(start).1, self.area(end).0))
+    }
+
+    // returns false if there was nothing to delete
+    pub fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+
+        let cursor_before = self.cursor;
+        let deleted = self.typed_text(start, end);
+        self.remove_range(start, end);
+        self.record(self.offset(start), deleted, String::new(), cursor_before);
+        true
+    }
+
+    pub fn selected_text(&self, mode: CopyMode) -> Option<String> {
+        let (start, end) = self.selection()?;
+        if mode == CopyMode::Typed {
+            return Some(self.typed_text(start, end));
+        }
+
+        // like `selection_range`, this doesn't include virtual whitespace at the start and end
+        let mut s = String::new();
+        for i in start.0..=end.0 {
+            let ws = &self.elmts[i].whitespace;
+            if i == end.0 {
+                let from = if i == start.0 { start.1 } else { 0 };
+                s.extend(ws.typed[from..end.1].iter().map(|x| x.as_char()));
+                break;
+            }
+            if i == start.0 {
+                if start.1 < ws.typed.len() {
+                    s.extend(ws.get_string().chars().skip(start.1));
+                }
+            } else {
+                s.push_str(&ws.get_string());
+            }
+            s.push(self.elmts[i].character);
+        }
+        Some(s)
+    }
+
+    // copies the selected text and deletes it
+    pub fn cut(&mut self, mode: CopyMode) -> Option<String> {
+        let text = self.selected_text(mode)?;
+        self.delete_selection();
+        Some(text)
+    }
+
+    // Inserts (formatted) code. Whitespace the formatter reproduces is removed, so it is displayed
+    // as virtual whitespace once the virtual whitespace is updated, see `from_formatted`. If the
+    // code can't be formatted, all whitespace that isn't needed to separate tokens is removed.
+    pub fn paste(&mut self, text: &str, formatter: &dyn Formatter, max_width: usize) {
+        let text = &dedent(text);
+        let typed = if formatter.format(text, max_width).is_some() {
+            Content::from_formatted(text, formatter, max_width).typed_string()
+        } else {
+            lexer::minimize_whitespace(text, &self.syntax)
+        };
+        self.insert_str(&typed);
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        self.delete_selection();
+        let cursor_before = self.cursor;
+        for c in text.chars() {
+            self.insert_typed(c);
+        }
+        self.record(
+            self.offset(cursor_before),
+            String::new(),
+            text.to_string(),
+            cursor_before,
+        );
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.history.undo() else {
+            return false;
+        };
+        self.replace_typed(edit.offset, edit.inserted.chars().count(), &edit.deleted);
+        self.cursor = edit.cursor_before;
+        self.spacial_cursor = self.cursor_pos().0;
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.history.redo() else {
+            return false;
+        };
+        self.replace_typed(edit.offset, edit.deleted.chars().count(), &edit.inserted);
+        self.cursor = edit.cursor_after;
+        self.spacial_cursor = self.cursor_pos().0;
+        true
+    }
+
+    fn record(
+        &mut self,
+        offset: usize,
+        deleted: String,
+        inserted: String,
+        cursor_before: (usize, usize),
+    ) {
+        self.history.record(Edit {
+            offset,
+            deleted,
+            inserted,
+            cursor_before,
+            cursor_after: self.cursor,
+        });
+    }
+
+    // offset of a cursor position in the typed text
+    fn offset(&self, pos: (usize, usize)) -> usize {
+        self.elmts.summary_before(pos.0).typed + pos.1
+    }
+
+    // cursor position of an offset in the typed text
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let i = self.elmts.search(|s| s.typed > offset);
+        if i < self.elmts.len() {
+            return (i, offset - self.elmts.summary_before(i).typed);
+        }
+        let last = self.elmts.len() - 1;
+        (last, self.elmts[last].whitespace.typed.len())
+    }
+
+    // typed text between two cursor positions
+    fn typed_text(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let mut s = String::new();
+        for i in start.0..=end.0 {
+            let typed = &self.elmts[i].whitespace.typed;
+            let from = if i == start.0 { start.1 } else { 0 };
+            let to = if i == end.0 { end.1 } else { typed.len() };
+            s.extend(typed[from..to].iter().map(|x| x.as_char()));
+            if i < end.0 {
+                s.push(self.elmts[i].character);
+            }
+        }
+        s
+    }
+
+    fn remove_range(&mut self, start: (usize, usize), end: (usize, usize)) {
+        if start.0 == end.0 {
+            self.elmts.update(start.0, |e| {
+                e.whitespace.typed.drain(start.1..end.1);
+            });
+        } else {
+            let mut typed = self.elmts[start.0].whitespace.typed[..start.1].to_vec();
+            typed.extend_from_slice(&self.elmts[end.0].whitespace.typed[end.1..]);
+            self.elmts.update(end.0, |e| e.whitespace.typed = typed);
+            self.elmts.remove_range(start.0..end.0);
+        }
+        self.anchor = None;
+        self.cursor = start;
+        self.spacial_cursor = self.cursor_pos().0;
+    }
+
+    // replaces `len` chars of the typed text at `offset`, without recording the edit
+    fn replace_typed(&mut self, offset: usize, len: usize, text: &str) {
+        self.remove_range(self.position(offset), self.position(offset + len));
+        for c in text.chars() {
+            self.insert_typed(c);
+        }
+    }
+
     pub fn cursor_left(&mut self) {
         if self.cursor.1 > 0 {
             self.cursor.1 -= 1;
@@ -253,11 +747,35 @@ impl Content {
         self.spacial_cursor = self.cursor_pos().0;
     }
 
+    // The first element whose displayed text reaches `line` and the visual position it starts at.
+    // The elements before it don't need to be scanned when looking for a position on the line.
+    fn line_start(&self, line: usize) -> (usize, (usize, usize)) {
+        let first = self.elmts.search(|s| s.lines >= line);
+        (first, self.elmts.summary_before(first).end())
+    }
+
+    // Moves the cursor to the end of the last element, for targets past the end of the text. As in
+    // `cursor_to`, the spacial cursor is the end of the cursor area if it doesn't start on the
+    // target line (e.g. on an empty last line).
+    fn cursor_to_last(&mut self, target: (usize, usize)) {
+        let last = self.elmts.len() - 1;
+        self.cursor = (
+            last,
+            self.elmts[last].whitespace.get_num_cursor_positions() - 1,
+        );
+        let area = self.cursor_pos();
+        self.spacial_cursor = if (area.0).0 == target.0 {
+            area.0
+        } else {
+            area.1
+        };
+    }
+
     pub fn cursor_down(&mut self) {
         let target = (self.spacial_cursor.0 + 1, self.spacial_cursor.1);
-        let mut line = 0;
-        let mut col = 0;
-        for (i, chars) in self.elmts.iter().map(|x| x.get_string()).enumerate() {
+        let (first, (mut line, mut col)) = self.line_start(target.0);
+        let elmts = self.elmts.iter_from(first).map(|x| x.get_string());
+        for (i, chars) in (first..).zip(elmts) {
             for (j, c) in chars.chars().enumerate() {
                 if line == target.0 {
                     self.cursor = (
@@ -277,14 +795,7 @@ impl Content {
                 }
             }
         }
-        self.cursor = (
-            self.elmts.len() - 1,
-            self.elmts[self.elmts.len() - 1]
-                .whitespace
-                .get_num_cursor_positions()
-                - 1,
-        );
-        self.spacial_cursor = self.cursor_pos().0;
+        self.cursor_to_last(target);
     }
 
     pub fn cursor_up(&mut self) {
@@ -293,9 +804,9 @@ impl Content {
         } else {
             (0, 0)
         };
-        let mut line = 0;
-        let mut col = 0;
-        for (i, chars) in self.elmts.iter().map(|x| x.get_string()).enumerate() {
+        let (first, (mut line, mut col)) = self.line_start(target.0);
+        let elmts = self.elmts.iter_from(first).map(|x| x.get_string());
+        for (i, chars) in (first..).zip(elmts) {
             for (j, c) in chars.chars().enumerate() {
                 if line == target.0 {
                     self.cursor = (
@@ -315,15 +826,14 @@ impl Content {
                 }
             }
         }
-
-        panic!("this shouldn't be possible!");
+        self.cursor_to_last(target);
     }
 
     pub fn cursor_home(&mut self) {
         let target = (self.spacial_cursor.0, 0);
-        let mut line = 0;
-        let mut col = 0;
-        for (i, chars) in self.elmts.iter().map(|x| x.get_string()).enumerate() {
+        let (first, (mut line, mut col)) = self.line_start(target.0);
+        let elmts = self.elmts.iter_from(first).map(|x| x.get_string());
+        for (i, chars) in (first..).zip(elmts) {
             for (j, c) in chars.chars().enumerate() {
                 if line == target.0 {
                     self.cursor = (
@@ -343,16 +853,15 @@ impl Content {
                 }
             }
         }
-
-        panic!("this shouldn't be possible!");
+        self.cursor_to_last(target);
     }
 
     pub fn cursor_end(&mut self) {
         let target = (self.spacial_cursor.0, self.spacial_cursor.1);
-        let mut line = 0;
-        let mut col = 0;
+        let (first, (mut line, mut col)) = self.line_start(target.0);
         let mut curr_col = 0;
-        for (i, chars) in self.elmts.iter().map(|x| x.get_string()).enumerate() {
+        let elmts = self.elmts.iter_from(first).map(|x| x.get_string());
+        for (i, chars) in (first..).zip(elmts) {
             for (j, c) in chars.chars().enumerate() {
                 if line == target.0 {
                     self.cursor = (
@@ -373,17 +882,22 @@ impl Content {
                 }
             }
         }
-        self.cursor = (
-            self.elmts.len() - 1,
-            self.elmts[self.elmts.len() - 1]
-                .whitespace
-                .get_num_cursor_positions()
-                - 1,
-        );
-        self.spacial_cursor = self.cursor_pos().0;
+        self.cursor_to_last(target);
     }
 
     pub fn insert(&mut self, c: char) {
+        self.delete_selection();
+        let cursor_before = self.cursor;
+        self.insert_typed(c);
+        self.record(
+            self.offset(cursor_before),
+            String::new(),
+            c.to_string(),
+            cursor_before,
+        );
+    }
+
+    fn insert_typed(&mut self, c: char) {
         // check for whitespace
         if c == '\n' || c == ' ' {
             let typed_len = self.elmts[self.cursor.0].whitespace.typed.len();
@@ -392,10 +906,9 @@ impl Content {
             } else {
                 WhitespaceChar::Space
             };
-            self.elmts[self.cursor.0]
-                .whitespace
-                .typed
-                .insert(std::cmp::min(self.cursor.1, typed_len), ws_char);
+            let idx = std::cmp::min(self.cursor.1, typed_len);
+            self.elmts
+                .update(self.cursor.0, |e| e.whitespace.typed.insert(idx, ws_char));
             self.cursor.1 += 1;
             self.spacial_cursor = self.cursor_pos().0;
             return;
@@ -412,19 +925,32 @@ impl Content {
                 virtual_spaces: 0,
             },
         };
-        self.elmts[self.cursor.0].whitespace.typed = ws_right;
+        self.elmts
+            .update(self.cursor.0, |e| e.whitespace.typed = ws_right);
         self.elmts.insert(self.cursor.0, new_elmt);
         self.cursor = (self.cursor.0 + 1, 0);
         self.spacial_cursor = self.cursor_pos().0;
     }
 
     pub fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        let cursor_before = self.cursor;
+        let offset = self.offset(self.cursor);
+        let deleted = if self.cursor.1 > 0 {
+            self.elmts[self.cursor.0].whitespace.typed[self.cursor.1 - 1].as_char()
+        } else if self.cursor.0 > 0 {
+            self.elmts[self.cursor.0 - 1].character
+        } else {
+            return;
+        };
+
         if self.cursor.1 > 0 {
             if self.cursor.1 <= self.elmts[self.cursor.0].whitespace.typed.len() {
-                self.elmts[self.cursor.0]
-                    .whitespace
-                    .typed
-                    .remove(self.cursor.1 - 1);
+                let idx = self.cursor.1 - 1;
+                self.elmts
+                    .update(self.cursor.0, |e| e.whitespace.typed.remove(idx));
             }
             self.cursor.1 -= 1;
             self.spacial_cursor = self.cursor_pos().1;
@@ -442,7 +968,7 @@ impl Content {
                     ws_right.virtual_spaces
                 },
             };
-            self.elmts[self.cursor.0].whitespace = ws_new;
+            self.elmts.update(self.cursor.0, |e| e.whitespace = ws_new);
             let cursor_new = (
                 self.cursor.0 - 1,
                 self.elmts[self.cursor.0 - 1]
@@ -454,14 +980,33 @@ impl Content {
             self.cursor = cursor_new;
             self.spacial_cursor = self.cursor_pos().1;
         }
+        self.record(
+            offset - 1,
+            deleted.to_string(),
+            String::new(),
+            cursor_before,
+        );
     }
 
     pub fn delete(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        let cursor_before = self.cursor;
+        let typed = &self.elmts[self.cursor.0].whitespace.typed;
+        let deleted = if self.cursor.1 < typed.len() {
+            typed[self.cursor.1].as_char()
+        } else if self.cursor.0 < self.elmts.len() - 1 {
+            self.elmts[self.cursor.0].character
+        } else {
+            return;
+        };
+        let offset = self.offset(self.cursor);
+
         if self.cursor.1 < self.elmts[self.cursor.0].whitespace.typed.len() {
-            self.elmts[self.cursor.0]
-                .whitespace
-                .typed
-                .remove(self.cursor.1);
+            let idx = self.cursor.1;
+            self.elmts
+                .update(self.cursor.0, |e| e.whitespace.typed.remove(idx));
         } else if self.cursor.0 < self.elmts.len() - 1 {
             let ws_left = &self.elmts[self.cursor.0].whitespace;
             let ws_right = &self.elmts[self.cursor.0 + 1].whitespace;
@@ -476,65 +1021,642 @@ impl Content {
                     ws_right.virtual_spaces
                 },
             };
-            self.elmts[self.cursor.0 + 1].whitespace = ws_new;
+            self.elmts
+                .update(self.cursor.0 + 1, |e| e.whitespace = ws_new);
             self.elmts.remove(self.cursor.0);
             self.cursor = (self.cursor.0, 0);
             self.spacial_cursor = self.cursor_pos().0;
         }
+        self.record(offset, deleted.to_string(), String::new(), cursor_before);
+    }
+
+    // The virtual (newlines, spaces) of every element, e.g. to transfer the result of formatting a
+    // copy of the content
+    pub fn virtual_whitespace(&self) -> Vec<(usize, usize)> {
+        self.elmts
+            .iter()
+            .map(|e| (e.whitespace.virtual_newlines, e.whitespace.virtual_spaces))
+            .collect()
+    }
+
+    // Sets the virtual whitespace of all elements, as returned by `virtual_whitespace` of a content
+    // with the same typed text. Returns false (and changes nothing) if the number of elements
+    // doesn't match.
+    pub fn set_virtual_whitespace(&mut self, virtual_whitespace: &[(usize, usize)]) -> bool {
+        let len = self.elmts.len();
+        if virtual_whitespace.len() != len {
+            return false;
+        }
+        let mut iter = virtual_whitespace.iter();
+        self.elmts.update_range(0..len, |e| {
+            (e.whitespace.virtual_newlines, e.whitespace.virtual_spaces) = *iter.next().unwrap();
+        });
+        self.update_cursor_after_format();
+        true
     }
 
     pub fn clear_virtual_whitespace(&mut self) {
-        for e in &mut self.elmts {
+        let len = self.elmts.len();
+        self.elmts.update_range(0..len, |e| {
             e.whitespace.virtual_newlines = 0;
             e.whitespace.virtual_spaces = 0;
-        }
+        });
     }
 
-    pub fn update_virtual_whitespace(&mut self) -> String {
-        // get string (without virtual whitespace)
-        let mut clone = self.clone();
-        clone.clear_virtual_whitespace();
-        let s = clone.get_string();
+    // Formats the typed text to lines of at most `max_width` chars and updates the virtual whitespace
+    pub fn update_virtual_whitespace(
+        &mut self,
+        formatter: &dyn Formatter,
+        max_width: usize,
+    ) -> String {
+        self.syntax = formatter.syntax();
+        // get string (without virtual whitespace and comments)
+        let (s, classes) = self.lex();
 
         // pass that string to prettyplease
-        match prettify_code(s.clone()) {
+        let msg = match formatter.format(&s, max_width) {
             Some(res) => {
-                self.update_virtual_whitespace_2(&res);
-                self.cursor.1 = std::cmp::min(
-                    self.cursor.1,
-                    self.elmts[self.cursor.0]
-                        .whitespace
-                        .get_num_cursor_positions()
-                        - 1,
-                );
-                self.spacial_cursor = self.cursor_pos().0;
+                let len = self.elmts.len();
+                let alignment = self
+                    .elmts
+                    .with_slice(0..len, |elmts| align_with_comments(elmts, &classes, &res));
                 format!(
-                    "Typed chars: {}, Displayed: {} ({}%)",
+                    "Typed chars: {}, Displayed: {} ({}%), inserted by formatter: {}, removed by formatter: {}",
                     s.len(),
                     res.len(),
-                    s.len() * 100 / res.len()
+                    s.len() * 100 / res.len().max(1), // empty for an empty file
+                    alignment.inserted.len(),
+                    alignment.deleted.len(),
                 )
             }
-            None => "error".to_string(),
-        }
-    }
-}
-
-impl Whitespace {
-    fn get_num_cursor_positions(&self) -> usize {
-        self.typed.len() + 1
+            None if formatter.formats_items() => {
+                self.update_virtual_whitespace_partial(&classes, formatter, max_width)
+            }
+            // formatting every item on its own would be slow for formatters like rustfmt, which
+            // run a process per call, so the virtual whitespace is kept
+            None => "Parse error, virtual whitespace not updated".to_string(),
+        };
+        self.update_cursor_after_format();
+        msg
     }
-}
 
-pub fn prettify_code(input: String) -> Option<String> {
-    let file = syn::parse_file(&input).ok()?;
-    let output = prettyplease::unparse(&file);
-    Some(output)
-}
+    // Like `update_virtual_whitespace`, but only reformats the top-level item (e.g. function, impl
+    // or struct) the cursor is in. The virtual whitespace of the other items is left untouched, so
+    // distant code doesn't shift while typing.
+    pub fn update_virtual_whitespace_at_cursor(
+        &mut self,
+        formatter: &dyn Formatter,
+        max_width: usize,
+    ) -> String {
+        self.syntax = formatter.syntax();
+        let (_, classes) = self.lex();
+        let items = self.items(&classes);
+        // the cursor is in front of its element, so the item of the previous element is edited
+        let idx = self.cursor.0.saturating_sub(1);
+        let Some(i) = items.iter().position(|range| range.contains(&idx)) else {
+            return self.update_virtual_whitespace(formatter, max_width);
+        };
+
+        let formatted =
+            self.format_item(&items, i, &classes, &mut |s| formatter.format(s, max_width));
+        let last = self.elmts.len() - 1;
+        if formatted && i == items.len() - 1 {
+            self.elmts.update(last, |e| {
+                e.whitespace.virtual_newlines = 1;
+                e.whitespace.virtual_spaces = 0;
+            });
+        }
+        self.update_cursor_after_format();
+        if formatted {
+            format!("Formatted item {} of {}", i + 1, items.len())
+        } else {
+            format!("Parse error in item {} of {}", i + 1, items.len())
+        }
+    }
+
+    // Like `update_virtual_whitespace`, but formats every top-level item on its own and reuses the
+    // output of items that are unchanged since the last update with the same `cache`. Falls back to
+    // `update_virtual_whitespace` for formatters that can't format items independently.
+    pub fn update_virtual_whitespace_cached(
+        &mut self,
+        formatter: &dyn Formatter,
+        max_width: usize,
+        cache: &mut FormatCache,
+    ) -> String {
+        if !formatter.formats_items() {
+            return self.update_virtual_whitespace(formatter, max_width);
+        }
+        self.syntax = formatter.syntax();
+        let (_, classes) = self.lex();
+        let items = self.items(&classes);
+
+        // only the items of this update are kept, so the cache doesn't grow while editing
+        let mut previous = std::mem::take(&mut cache.items);
+        if cache.max_width != max_width {
+            previous.clear();
+            cache.max_width = max_width;
+        }
+        let mut num_formatted = 0;
+        let mut num_cached = 0;
+        let mut last_formatted = false;
+        for i in 0..items.len() {
+            last_formatted = self.format_item(&items, i, &classes, &mut |s| {
+                let res = match cache.items.get(s).or(previous.get(s)) {
+                    Some(res) => {
+                        num_cached += 1;
+                        res.clone()
+                    }
+                    None => formatter.format(s, max_width),
+                };
+                cache.items.insert(s.to_string(), res.clone());
+                res
+            });
+            if last_formatted {
+                num_formatted += 1;
+            }
+        }
+
+        if last_formatted {
+            let last = self.elmts.len() - 1;
+            self.elmts.update(last, |e| {
+                e.whitespace.virtual_newlines = 1;
+                e.whitespace.virtual_spaces = 0;
+            });
+        }
+        self.update_cursor_after_format();
+        format!(
+            "Formatted {} of {} items ({} cached)",
+            num_formatted,
+            items.len(),
+            num_cached
+        )
+    }
+
+    fn update_cursor_after_format(&mut self) {
+        self.cursor.1 = std::cmp::min(
+            self.cursor.1,
+            self.elmts[self.cursor.0]
+                .whitespace
+                .get_num_cursor_positions()
+                - 1,
+        );
+        self.spacial_cursor = self.cursor_pos().0;
+    }
+
+    // Fallback for code that doesn't parse as a whole, if the formatter formats items: every
+    // top-level item that parses on its own is formatted, the items that don't keep the virtual
+    // whitespace of the last successful update.
+    fn update_virtual_whitespace_partial(
+        &mut self,
+        classes: &[ElmtClass],
+        formatter: &dyn Formatter,
+        max_width: usize,
+    ) -> String {
+        let items = self.items(classes);
+        let mut num_formatted = 0;
+        let mut last_formatted = false;
+        for i in 0..items.len() {
+            last_formatted =
+                self.format_item(&items, i, classes, &mut |s| formatter.format(s, max_width));
+            if last_formatted {
+                num_formatted += 1;
+            }
+        }
+
+        if last_formatted {
+            let last = self.elmts.len() - 1;
+            self.elmts.update(last, |e| {
+                e.whitespace.virtual_newlines = 1;
+                e.whitespace.virtual_spaces = 0;
+            });
+        }
+
+        format!(
+            "Parse error, formatted {} of {} items",
+            num_formatted,
+            items.len()
+        )
+    }
+
+    // the top-level items of the content, excluding the terminating '\0'
+    fn items(&self, classes: &[ElmtClass]) -> Vec<std::ops::Range<usize>> {
+        let chars: Vec<char> = self.elmts.iter().map(|e| e.character).collect();
+        let opaque: Vec<bool> = classes.iter().map(|c| *c != ElmtClass::Code).collect();
+        let last = self.elmts.len() - 1;
+        split_items(&chars[..last], &opaque[..last])
+    }
+
+    // Formats the item `items[i]` on its own and returns whether it could be formatted. The item
+    // is separated from the previous one by a virtual newline in any case.
+    fn format_item(
+        &mut self,
+        items: &[std::ops::Range<usize>],
+        i: usize,
+        classes: &[ElmtClass],
+        format: &mut dyn FnMut(&str) -> Option<String>,
+    ) -> bool {
+        let range = items[i].clone();
+        let mut s = String::new();
+        let elmts = self.elmts.iter_from(range.start).take(range.len());
+        for (j, e) in elmts.enumerate() {
+            if j > 0 {
+                s.extend(e.whitespace.typed.iter().map(|x| x.get_string()));
+            }
+            s.push(if classes[range.start + j].is_comment() {
+                ' '
+            } else {
+                e.character
+            });
+        }
+
+        let formatted = match format(&s) {
+            Some(res) => {
+                self.elmts.with_slice(range.clone(), |elmts| {
+                    align_with_comments(elmts, &classes[range.clone()], &res)
+                });
+                true
+            }
+            None => false,
+        };
+
+        // every item starts on a new line, except for trailing comments of the previous item
+        let class = classes[range.start];
+        self.elmts.update(range.start, |e| {
+            let first = &mut e.whitespace;
+            let trailing_comment = i > 0
+                && matches!(class, ElmtClass::CommentStart { .. })
+                && !first.typed.iter().any(|x| x.is_newline());
+            (first.virtual_newlines, first.virtual_spaces) = if i == 0 {
+                (0, 0)
+            } else if trailing_comment {
+                (0, 1)
+            } else {
+                (1, 0)
+            };
+        });
+        formatted
+    }
+
+    // Returns the typed text with comments replaced by spaces and the class of every element.
+    fn lex(&self) -> (String, Vec<ElmtClass>) {
+        let mut chars = vec![];
+        let mut char_idx = vec![];
+        for e in &self.elmts {
+            chars.extend(e.whitespace.typed.iter().map(|x| x.as_char()));
+            char_idx.push(chars.len());
+            if e.character != '\0' {
+                chars.push(e.character);
+            }
+        }
+
+        let mut char_classes = vec![ElmtClass::Code; chars.len()];
+        for token in lexer::tokenize(&chars, &self.syntax) {
+            let (first, rest) = match token.kind {
+                TokenKind::LineComment => {
+                    (ElmtClass::CommentStart { line: true }, ElmtClass::Comment)
+                }
+                TokenKind::BlockComment => {
+                    (ElmtClass::CommentStart { line: false }, ElmtClass::Comment)
+                }
+                k if k.is_literal() => (ElmtClass::Literal, ElmtClass::Literal),
+                _ => continue,
+            };
+            char_classes[token.start] = first;
+            for c in &mut char_classes[token.start + 1..token.end] {
+                *c = rest;
+            }
+        }
+
+        let s = chars
+            .iter()
+            .zip(&char_classes)
+            .map(|(c, class)| if class.is_comment() { ' ' } else { *c })
+            .collect();
+        let classes = char_idx
+            .into_iter()
+            .map(|i| char_classes.get(i).copied().unwrap_or(ElmtClass::Code))
+            .collect();
+        (s, classes)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ElmtClass {
+    Code,
+    Literal, // part of a string or char literal
+    CommentStart { line: bool },
+    Comment,
+}
+
+impl ElmtClass {
+    fn is_comment(self) -> bool {
+        matches!(self, ElmtClass::CommentStart { .. } | ElmtClass::Comment)
+    }
+}
+
+impl Whitespace {
+    fn get_num_cursor_positions(&self) -> usize {
+        self.typed.len() + 1
+    }
+
+    // visual start and end of cursor position `idx`, given the position the whitespace starts at
+    fn area(&self, (mut line, mut col): (usize, usize), idx: usize) -> CursorPos {
+        let virtual_end = (
+            line + self.virtual_newlines,
+            if self.virtual_newlines == 0 { col } else { 0 } + self.virtual_spaces,
+        );
+
+        for wc in self.typed.iter().take(idx) {
+            match wc {
+                WhitespaceChar::Space => col += 1,
+                WhitespaceChar::Newline => {
+                    col = 0;
+                    line += 1;
+                }
+            }
+        }
+
+        let start = (line, col);
+        let end = if idx == self.get_num_cursor_positions() - 1 {
+            std::cmp::max(start, virtual_end)
+        } else {
+            start
+        };
+
+        (start, end)
+    }
+}
+
+// Aligns the typed elements with the formatted text by matching their non-whitespace characters
+// (see `diff::matches`). Each element gets the whitespace preceding its match in the formatted text.
+// Characters only present in the formatted text (e.g. trailing commas added by the formatter)
+// contribute their whitespace to the next element, typed characters removed by the formatter take
+// the place of the formatted characters they replace, if any.
+fn align_virtual_whitespace(elmts: &mut [Elmt], formatted_input: &str) -> Alignment {
+    // non-whitespace chars of the formatted text with their index and the whitespace preceding them
+    let mut formatted = vec![];
+    let (mut newlines, mut spaces) = (0, 0);
+    for (idx, c) in formatted_input.chars().enumerate() {
+        match c {
+            '\n' => {
+                newlines += 1;
+                spaces = 0;
+            }
+            ' ' => spaces += 1,
+            c => {
+                formatted.push((c, idx, (newlines, spaces)));
+                (newlines, spaces) = (0, 0);
+            }
+        }
+    }
+    let trailing = (newlines, spaces);
+
+    let typed: Vec<char> = elmts.iter().map(|e| e.character).collect();
+    let formatted_chars: Vec<char> = formatted.iter().map(|f| f.0).collect();
+    let matches = diff::matches(&typed, &formatted_chars);
+
+    let mut alignment = Alignment {
+        positions: vec![None; elmts.len()],
+        inserted: vec![],
+        deleted: vec![],
+    };
+    let mut matched = vec![None; elmts.len()];
+    for (i, j) in &matches {
+        matched[*i] = Some(*j);
+    }
+
+    // appends the whitespace `b` to `a`
+    let append = |a: (usize, usize), b: (usize, usize)| {
+        if b.0 > 0 {
+            (a.0 + b.0, b.1)
+        } else {
+            (a.0, a.1 + b.1)
+        }
+    };
+
+    // index of the next formatted char that hasn't been assigned to an element
+    let mut next = 0;
+    for (i, elmt) in elmts.iter_mut().enumerate() {
+        let ws = match matched[i] {
+            Some(j) => {
+                let mut ws = (0, 0);
+                for (_, idx, run) in &formatted[next..j] {
+                    alignment.inserted.push(*idx);
+                    ws = append(ws, *run);
+                }
+                next = j + 1;
+                alignment.positions[i] = Some(formatted[j].1);
+                append(ws, formatted[j].2)
+            }
+            None if elmt.character == '\0' => {
+                // end of the file
+                let mut ws = (0, 0);
+                for (_, idx, run) in &formatted[next..] {
+                    alignment.inserted.push(*idx);
+                    ws = append(ws, *run);
+                }
+                next = formatted.len();
+                append(ws, trailing)
+            }
+            None => {
+                alignment.deleted.push(i);
+                let next_match = matched[i..].iter().flatten().next().copied();
+                if next < next_match.unwrap_or(formatted.len()) {
+                    // the typed char replaces a formatted one
+                    alignment.inserted.push(formatted[next].1);
+                    next += 1;
+                    formatted[next - 1].2
+                } else {
+                    (0, 0)
+                }
+            }
+        };
+        (
+            elmt.whitespace.virtual_newlines,
+            elmt.whitespace.virtual_spaces,
+        ) = ws;
+    }
+    alignment
+        .inserted
+        .extend(formatted[next..].iter().map(|f| f.1));
+
+    alignment
+}
+
+// The formatter doesn't know about comments, so they are aligned around the formatted code: Code
+// elements are aligned as usual, comments get the whitespace of the code following them. Trailing
+// comments stay on their line.
+fn align_with_comments(
+    elmts: &mut [Elmt],
+    classes: &[ElmtClass],
+    formatted_input: &str,
+) -> Alignment {
+    if !classes.iter().any(|c| c.is_comment()) {
+        return align_virtual_whitespace(elmts, formatted_input);
+    }
+
+    let code: Vec<usize> = (0..elmts.len())
+        .filter(|i| !classes[*i].is_comment())
+        .collect();
+    let mut code_elmts: Vec<Elmt> = code.iter().map(|i| elmts[*i].clone()).collect();
+    let code_alignment = align_virtual_whitespace(&mut code_elmts, formatted_input);
+    for (i, e) in code.iter().zip(code_elmts) {
+        elmts[*i].whitespace = e.whitespace;
+    }
+
+    let mut alignment = Alignment {
+        positions: vec![None; elmts.len()],
+        inserted: code_alignment.inserted,
+        deleted: code_alignment.deleted.iter().map(|i| code[*i]).collect(),
+    };
+    for (i, pos) in code.iter().zip(code_alignment.positions) {
+        alignment.positions[*i] = pos;
+    }
+
+    let mut i = 0;
+    while i < elmts.len() {
+        if !classes[i].is_comment() {
+            i += 1;
+            continue;
+        }
+
+        let next_code = (i..elmts.len()).find(|j| !classes[*j].is_comment());
+        let (newlines, spaces) = next_code.map_or((0, 0), |j| {
+            let ws = &elmts[j].whitespace;
+            (ws.virtual_newlines, ws.virtual_spaces)
+        });
+
+        let mut after_line_comment = false;
+        for j in i..next_code.unwrap_or(elmts.len()) {
+            let ws = &mut elmts[j].whitespace;
+            let typed_newline = ws.typed.iter().any(|x| x.is_newline());
+            (ws.virtual_newlines, ws.virtual_spaces) = match classes[j] {
+                ElmtClass::CommentStart { .. } if after_line_comment => (1, spaces),
+                ElmtClass::CommentStart { .. } if j > 0 && newlines > 0 && !typed_newline => (0, 1),
+                ElmtClass::CommentStart { .. } => (newlines, spaces),
+                _ => (0, 0),
+            };
+            if let ElmtClass::CommentStart { line } = classes[j] {
+                after_line_comment = line;
+            }
+        }
+
+        if let Some(j) = next_code {
+            if after_line_comment || newlines > 0 {
+                elmts[j].whitespace.virtual_newlines = 1;
+            }
+        }
+        i = next_code.unwrap_or(elmts.len());
+    }
+
+    alignment
+}
+
+// Splits the characters of a file into top-level items (ranges of indices into `chars`). An item
+// ends with a `;` or a closing brace on the top level, unless the brace is followed by something
+// that continues the expression (e.g. `;` or `else`). Opaque characters (literals and comments) are
+// ignored.
+fn split_items(chars: &[char], opaque: &[bool]) -> Vec<std::ops::Range<usize>> {
+    let mut items = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+
+    for i in 0..chars.len() {
+        if opaque[i] {
+            continue;
+        }
+        match chars[i] {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            '}' => {
+                depth = depth.saturating_sub(1);
+                let rest: Vec<char> = (i + 1..chars.len())
+                    .filter(|j| !opaque[*j])
+                    .take(5)
+                    .map(|j| chars[j])
+                    .collect();
+                if depth == 0 && !continues_expression(&rest) {
+                    items.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            ';' if depth == 0 => {
+                items.push(start..i + 1);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    if start < chars.len() {
+        items.push(start..chars.len());
+    }
+
+    items
+}
+
+fn continues_expression(rest: &[char]) -> bool {
+    match rest.first() {
+        None | Some('#') | Some('}') => false,
+        Some(c) if c.is_alphanumeric() || *c == '_' => {
+            rest.starts_with(&['e', 'l', 's', 'e'])
+                && !rest
+                    .get(4)
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+        }
+        Some(_) => true,
+    }
+}
+
+// Removes the indentation the lines after the first one have in common, which comes from the
+// context the text was copied from. The first line is usually copied without its indentation.
+fn dedent(text: &str) -> String {
+    let mut lines = text.split('\n');
+    let first = lines.next().unwrap_or_default();
+    let rest: Vec<&str> = lines.collect();
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let mut out = first.to_string();
+    for line in rest {
+        out.push('\n');
+        out.push_str(
+            line.get(indent..)
+                .unwrap_or(line.trim_start_matches([' ', '\t'])),
+        );
+    }
+    out
+}
+
+// Splits the input into its elements (including the terminating '\0') and the whitespace preceding
+// them, like `Content::from_string`. '\r' is dropped and other whitespace between tokens (e.g.
+// tabs) is replaced by spaces, as in `lexer::minimize_whitespace`.
+fn split_whitespace(input: &str, syntax: &Syntax) -> Vec<(char, Vec<WhitespaceChar>)> {
+    let chars: Vec<char> = input.chars().filter(|c| *c != '\r').collect();
+    let mut res = vec![];
+    let mut current = vec![];
+    for token in lexer::tokenize(&chars, syntax) {
+        for &c in &chars[token.start..token.end] {
+            match c {
+                ' ' => current.push(WhitespaceChar::Space),
+                '\n' => current.push(WhitespaceChar::Newline),
+                _ if token.kind == TokenKind::Whitespace => current.push(WhitespaceChar::Space),
+                other => res.push((other, std::mem::take(&mut current))),
+            }
+        }
+    }
+    res.push(('\0', current));
+    res
+}
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)] // the original tests pass `&&str`
 mod tests {
     use super::*;
+    use crate::format::{Prettyplease, DEFAULT_MAX_WIDTH};
 
     #[test]
     fn test() {
@@ -575,6 +1697,301 @@ mod tests {
         assert_eq!(&s, out);
     }
 
+    #[test]
+    fn test_alignment_report() {
+        let mut c = Content::from_string("let x = [a,b,c]");
+        let alignment = c.update_virtual_whitespace_2("let x = [a, b, c, ]");
+        assert_eq!(alignment.inserted, vec![16]);
+        assert_eq!(alignment.deleted, Vec::<usize>::new());
+        assert_eq!(alignment.positions[9], Some(13));
+        assert_eq!(alignment.positions[11], Some(18));
+    }
+
+    #[test]
+    fn test_visible_replaces_char() {
+        let mut c = Content::from_string("a+b");
+        let alignment = c.update_virtual_whitespace_2("a - b");
+        assert_eq!(&c.get_string(), "a + b");
+        assert_eq!(alignment.deleted, vec![1]);
+        assert_eq!(alignment.inserted, vec![2]);
+    }
+
+    #[test]
+    fn test_alignment_continues_after_inserted_chars() {
+        let typed = "fn a(){x}fn b(){y}";
+        let visible = "fn a() {\n    x;\n}\nfn b() {\n    y\n}\n";
+        let out = "fn a() {\n    x\n}\nfn b() {\n    y\n}\n";
+        let c = Content::from_strings(typed, visible);
+        assert_eq!(&c.get_string(), out);
+    }
+
+    #[test]
+    fn test_reordered_attributes() {
+        let typed = "#[b]#[a]fn f(){}";
+        let visible = "#[a]\n#[b]\nfn f() {}\n";
+        let c = Content::from_strings(typed, visible);
+        assert_eq!(&c.get_string(), "#[b]\n#[a]\nfn f() {}\n");
+    }
+
+    #[test]
+    fn test_update_partially_parsing_code() {
+        let mut content = Content::from_string("fn a(){let x=1;}fn b(){let y=");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            &content.get_string(),
+            "fn a() {\n    let x = 1;\n}\nfn b(){let y="
+        );
+    }
+
+    #[test]
+    fn test_update_empty() {
+        let mut content = Content::from_string("");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(content.get_string(), "");
+    }
+
+    #[test]
+    fn test_partial_update_needs_item_formatter() {
+        // formats whole files only, like rustfmt
+        struct WholeFile(std::cell::Cell<usize>);
+        impl Formatter for WholeFile {
+            fn format(&self, input: &str, max_width: usize) -> Option<String> {
+                self.0.set(self.0.get() + 1);
+                Prettyplease.format(input, max_width)
+            }
+        }
+        let formatter = WholeFile(Default::default());
+        let mut content = Content::from_string("fn a(){let x=1;}fn b(){let y=");
+        content.update_virtual_whitespace(&formatter, DEFAULT_MAX_WIDTH);
+        assert_eq!(formatter.0.get(), 1);
+        assert_eq!(&content.get_string(), "fn a(){let x=1;}fn b(){let y=");
+    }
+
+    #[test]
+    fn test_parse_error_keeps_virtual_whitespace() {
+        let mut content = Content::from_string("fn a(){let x=1;}");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(&content.get_string(), "fn a() {\n    let x = 1;\n}\n");
+        for _ in 0..14 {
+            content.cursor_right();
+        }
+        content.insert('+');
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(&content.get_string(), "fn a() {\n    let x = 1+;\n}\n");
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut content = Content::from_string(
+            "fn a(){\n// hi\nlet x=1;// trailing\n/* block */let y=\"// no\";f(/*x*/1)}",
+        );
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            &content.get_string(),
+            "fn a() {\n    // hi\n    let x = 1; // trailing\n    /* block */\n    let y = \"// no\";\n    f(/*x*/1)\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let mut content = Content::from_string(
+            "//! crate\n/// Doc\n#[derive(Debug)]\nstruct A{a:u8}// end\nfn b(){}",
+        );
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            &content.get_string(),
+            "//! crate\n/// Doc\n#[derive(Debug)]\nstruct A {\n    a: u8\n} // end\nfn b() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_partially_parsing_code() {
+        let mut content = Content::from_string("fn a(){x+1// c\n}// d\nfn b(");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            &content.get_string(),
+            "fn a() {\n    x + 1 // c\n} // d\nfn b("
+        );
+    }
+
+    #[test]
+    fn test_split_items() {
+        let chars: Vec<char> = "usea::{b,c};fn a(){if x{}else{}}structS{a:[u8;2]}"
+            .chars()
+            .collect();
+        let items: Vec<String> = split_items(&chars, &vec![false; chars.len()])
+            .into_iter()
+            .map(|r| chars[r].iter().collect())
+            .collect();
+        assert_eq!(
+            items,
+            vec!["usea::{b,c};", "fn a(){if x{}else{}}", "structS{a:[u8;2]}"]
+        );
+    }
+
+    #[test]
+    fn test_typed_string() {
+        let typed = "fn test(&self,other:&mut usize){let x=(self+1)*other;\n}\n";
+        let visible = "fn test(&self, other: &mut usize) {\n    let x = (self + 1) * other;\n}\n";
+        let c = Content::from_strings(typed, visible);
+        assert_eq!(&c.typed_string(), typed);
+    }
+
+    #[test]
+    fn test_from_formatted() {
+        let input = "pub fn a(x: u8) -> u8 {\n    // c\n    x + 1\n}\n";
+        let c = Content::from_formatted(input, &Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(c.get_string(), input);
+        assert_eq!(c.typed_string(), "pub fn a(x:u8)->u8{\n// c\nx+1}");
+    }
+
+    #[test]
+    fn test_from_formatted_keeps_extra_whitespace() {
+        let input = "fn a() {\r\n    let x = 1;\n\n\tx\n}\n";
+        let c = Content::from_formatted(input, &Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(c.get_string(), "fn a() {\n    let x = 1;\n\n x\n}\n");
+        assert_eq!(c.typed_string(), "fn a(){let x=1;\n\n x}");
+    }
+
+    #[test]
+    fn test_fragment() {
+        let mut c = Content::from_string("let x=a+1;x*2");
+        c.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(c.get_string(), "let x = a + 1;\nx * 2\n");
+        assert_eq!(c.typed_string(), "let x=a+1;x*2");
+    }
+
+    #[test]
+    fn test_update_at_cursor() {
+        let mut c = Content::from_string("fn a(){1}fn b(){2}");
+        c.cursor_to((0, 16));
+        c.update_virtual_whitespace_at_cursor(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(c.get_string(), "fn a(){1}\nfn b() {\n    2\n}\n");
+
+        c.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        c.cursor_to((1, 4));
+        c.insert('x');
+        c.insert('+');
+        c.update_virtual_whitespace_at_cursor(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            c.get_string(),
+            "fn a() {\n    x + 1\n}\nfn b() {\n    2\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_cached() {
+        let typed = "use a::b;\n// c\n#[derive(Debug)]struct S{x:u8}impl S{fn f(&self)->u8{if self.x>1{1}else{2}}}fn g(){}";
+        let mut full = Content::from_string(typed);
+        full.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        let mut cache = FormatCache::default();
+        let mut c = Content::from_string(typed);
+        let msg = c.update_virtual_whitespace_cached(&Prettyplease, DEFAULT_MAX_WIDTH, &mut cache);
+        assert_eq!(msg, "Formatted 4 of 4 items (0 cached)");
+        assert_eq!(c.get_string(), full.get_string());
+
+        c.cursor_to((0, 0));
+        c.insert('x');
+        let msg = c.update_virtual_whitespace_cached(&Prettyplease, DEFAULT_MAX_WIDTH, &mut cache);
+        assert_eq!(msg, "Formatted 3 of 4 items (3 cached)");
+        assert!(c
+            .get_string()
+            .starts_with("xuse a::b;\n// c\n#[derive(Debug)]\nstruct S {\n"));
+        let msg = c.update_virtual_whitespace_cached(&Prettyplease, 60, &mut cache);
+        assert_eq!(msg, "Formatted 3 of 4 items (0 cached)");
+    }
+
+    #[test]
+    fn test_visual_pos() {
+        let mut c = Content::from_strings("fn a(){\n1}", "fn a() {\n    1\n}");
+        assert_eq!(c.num_elmts(), 9);
+        assert_eq!(c.visual_pos(0), (0, 0));
+        assert_eq!(c.visual_pos(6), (1, 4));
+        assert_eq!(c.visual_pos(7), (2, 0));
+        assert_eq!(c.visual_pos(8), (2, 1));
+        assert_eq!(c.elmt_at(1, 4), Some(6));
+        assert_eq!(c.elmt_at(0, 6), None);
+        assert_eq!(c.elmt_at(1, 2), None);
+        assert_eq!(c.elmt_at(5, 0), None);
+
+        // the positions follow edits and changes of the virtual whitespace
+        c.cursor_to((0, 0));
+        c.insert('x');
+        assert_eq!(c.visual_pos(7), (1, 4));
+        c.clear_virtual_whitespace();
+        assert_eq!(c.visual_pos(7), (1, 0));
+        assert_eq!(c.elmt_at(1, 0), Some(7));
+    }
+
+    #[test]
+    fn test_set_virtual_whitespace() {
+        let mut formatted = Content::from_string("fn a(){1}");
+        formatted.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        let mut c = Content::from_string(&formatted.typed_string());
+        assert!(c.set_virtual_whitespace(&formatted.virtual_whitespace()));
+        assert_eq!(c.get_string(), "fn a() {\n    1\n}\n");
+        assert!(!c.set_virtual_whitespace(&[(0, 0)]));
+    }
+
+    #[test]
+    fn test_lines() {
+        use SpanKind::*;
+        let c = Content::from_strings("fn a(){\n1}", "fn a() {\n    1\n}");
+        let span = |text: &str, kind, elmt| Span {
+            text: text.to_string(),
+            kind,
+            elmt,
+            highlight: None,
+        };
+        let highlighted = |text: &str, elmt, highlight| Span {
+            highlight: Some(highlight),
+            ..span(text, Token, elmt)
+        };
+        let lines = c.lines();
+        assert_eq!(
+            lines,
+            vec![
+                vec![
+                    highlighted("fn", 0, Highlight::Keyword),
+                    span(" ", TypedWhitespace, 2),
+                    span("a()", Token, 2),
+                    span(" ", VirtualWhitespace, 5),
+                    span("{", Token, 5),
+                    span("\n", TypedWhitespace, 6),
+                ],
+                vec![
+                    span("    ", VirtualWhitespace, 6),
+                    highlighted("1", 6, Highlight::Literal),
+                    span("\n", VirtualWhitespace, 7),
+                ],
+                vec![span("}", Token, 7)],
+            ]
+        );
+        let text: String = lines.iter().flatten().map(|s| s.text.as_str()).collect();
+        assert_eq!(text, c.get_string());
+    }
+
+    #[test]
+    fn test_visible_whitespace() {
+        assert_eq!(visible_whitespace("fn a() {\n    1"), "fn·a()·{¶\n····1");
+    }
+
+    #[test]
+    fn test_token_whitespace() {
+        let c = Content::from_strings("pub fn a(){\n\"x y\"}", "pub fn a() {\n    \"x y\"\n}");
+        let tokens = c.token_whitespace();
+        let ws = |t: &TokenWhitespace| (t.typed.clone(), t.virtual_whitespace.clone());
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(tokens[1].token, "fn");
+        assert_eq!(ws(&tokens[1]), (" ".into(), "".into()));
+        assert_eq!(tokens[5].token, "{");
+        assert_eq!(ws(&tokens[5]), ("".into(), " ".into()));
+        assert_eq!(tokens[6].token, "\"x y\"");
+        assert_eq!(tokens[6].pos, (1, 4));
+        assert_eq!(ws(&tokens[6]), ("\n".into(), "    ".into()));
+        assert_eq!(tokens[7].pos, (2, 0));
+    }
+
     #[test]
     fn test_num_cursor_positions() {
         let ws = Whitespace {
@@ -639,6 +2056,232 @@ mod tests {
         assert_eq!(ws.get_num_cursor_positions(), 5);
     }
 
+    #[test]
+    fn test_delete_selection() {
+        let mut content = Content::from_string("ab \n c d");
+        content.cursor_right();
+        content.set_selecting(true);
+        for _ in 0..3 {
+            content.cursor_right();
+        }
+        assert_eq!(content.selection(), Some(((1, 0), (2, 2))));
+        content.backspace();
+        assert_eq!(&content.get_string(), "a c d");
+        assert_eq!(content.selection(), None);
+
+        // selecting backwards
+        content.cursor_end();
+        content.set_selecting(true);
+        content.cursor_left();
+        content.insert('x');
+        assert_eq!(&content.get_string(), "a c x");
+    }
+
+    #[test]
+    fn test_select_all() {
+        let mut content = Content::from_string("fn a() {}\n");
+        content.select_all();
+        content.insert('b');
+        assert_eq!(&content.get_string(), "b");
+    }
+
+    #[test]
+    fn test_selection_range() {
+        let mut content = Content::from_strings("a=b", "a = b");
+        content.set_selecting(true);
+        assert_eq!(content.selection_range(), None);
+        content.cursor_right();
+        assert_eq!(content.selection_range(), Some(((0, 0), (0, 1))));
+        content.cursor_right();
+        assert_eq!(content.selection_range(), Some(((0, 0), (0, 3))));
+        content.set_selecting(false);
+        assert_eq!(content.selection_range(), None);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut content = Content::from_string("fn a(){}");
+        for _ in 0..7 {
+            content.cursor_right();
+        }
+        for c in "let x".chars() {
+            content.insert(c);
+        }
+        content.backspace();
+        content.backspace();
+        assert_eq!(&content.get_string(), "fn a(){let}");
+
+        assert!(content.undo());
+        assert_eq!(&content.get_string(), "fn a(){let x}");
+        assert_eq!(content.cursor, (10, 0));
+        assert!(content.undo());
+        assert_eq!(&content.get_string(), "fn a(){}");
+        assert_eq!(content.cursor, (6, 0));
+        assert!(!content.undo());
+
+        assert!(content.redo());
+        assert_eq!(&content.get_string(), "fn a(){let x}");
+        assert!(content.redo());
+        assert_eq!(&content.get_string(), "fn a(){let}");
+        assert!(!content.redo());
+    }
+
+    #[test]
+    fn test_undo_selection() {
+        let mut content = Content::from_string("a b\nc");
+        content.select_all();
+        content.insert('x');
+        content.insert('y');
+        assert_eq!(&content.get_string(), "xy");
+        content.undo();
+        assert_eq!(&content.get_string(), "a b\nc");
+        content.redo();
+        assert_eq!(&content.get_string(), "xy");
+    }
+
+    #[test]
+    fn test_undo_delete() {
+        let mut content = Content::from_string("ab \ncd");
+        content.cursor_right();
+        content.delete();
+        content.delete();
+        content.delete();
+        assert_eq!(&content.get_string(), "acd");
+        content.undo();
+        assert_eq!(&content.get_string(), "ab \ncd");
+        assert_eq!(content.cursor, (1, 0));
+    }
+
+    #[test]
+    fn test_cursor_to() {
+        let mut content = Content::from_strings("fn a(){x}", "fn a() {\n    x\n}");
+        content.cursor_to((0, 3));
+        assert_eq!(content.cursor, (2, 1));
+        // inside virtual whitespace
+        content.cursor_to((1, 2));
+        assert_eq!(content.cursor, (6, 0));
+        assert_eq!(content.cursor_pos_2().1, (1, 4));
+        content.cursor_to((0, 100));
+        assert_eq!(content.cursor, (6, 0));
+        assert_eq!(content.cursor_pos_2().1, (0, 8));
+        content.cursor_to((5, 0));
+        assert_eq!(content.cursor, (8, 0));
+    }
+
+    #[test]
+    fn test_cursor_to_last_line() {
+        let mut content = Content::from_string("fn a(){x}");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(content.get_string(), "fn a() {\n    x\n}\n");
+        // below the last line, which is empty
+        content.cursor_to((5, 0));
+        assert_eq!(content.cursor, (8, 0));
+        assert_eq!(content.spacial_cursor, (3, 0));
+        content.cursor_home();
+        assert_eq!(content.cursor, (8, 0));
+        assert_eq!(content.spacial_cursor, (3, 0));
+        content.cursor_up();
+        assert_eq!(content.cursor, (7, 0));
+        assert_eq!(content.spacial_cursor, (2, 0));
+    }
+
+    #[test]
+    fn test_cursor_on_empty_lines() {
+        let mut c = Content::from_string("");
+        c.cursor_up();
+        c.cursor_home();
+        assert_eq!(c.cursor, (0, 0));
+
+        let mut c = Content::from_string("a\n");
+        c.cursor_down();
+        c.cursor_home();
+        assert_eq!(c.cursor_pos().0, (1, 0));
+        c.cursor_up();
+        assert_eq!(c.cursor_pos().0, (0, 0));
+    }
+
+    #[test]
+    fn test_cursor_to_selects() {
+        let mut content = Content::from_strings("a=b", "a = b");
+        content.cursor_to((0, 1));
+        content.set_selecting(true);
+        content.cursor_to((0, 5));
+        content.delete();
+        assert_eq!(&content.get_string(), "a");
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut content = Content::from_strings("fn a(){x;\ny}", "fn a() {\n    x;\n    y\n}");
+        content.select_all();
+        assert_eq!(
+            content.selected_text(CopyMode::Visible).as_deref(),
+            Some("fn a() {\n    x;\n    y\n}")
+        );
+        assert_eq!(
+            content.selected_text(CopyMode::Typed).as_deref(),
+            Some("fn a(){x;\ny}")
+        );
+
+        content.set_selecting(false);
+        content.cursor_to((1, 0));
+        content.set_selecting(true);
+        content.cursor_to((2, 5));
+        assert_eq!(
+            content.selected_text(CopyMode::Visible).as_deref(),
+            Some("x;\n    y")
+        );
+        assert_eq!(content.cut(CopyMode::Typed).as_deref(), Some("x;\ny"));
+        assert_eq!(&content.get_string(), "fn a() {\n}");
+        assert_eq!(content.selected_text(CopyMode::Visible), None);
+    }
+
+    #[test]
+    fn test_copy_within_whitespace() {
+        let mut content = Content::from_string("a    b");
+        content.anchor = Some((1, 1));
+        content.cursor = (1, 3);
+        assert_eq!(
+            content.selected_text(CopyMode::Visible).as_deref(),
+            Some("  ")
+        );
+        assert_eq!(
+            content.selected_text(CopyMode::Typed).as_deref(),
+            Some("  ")
+        );
+    }
+
+    #[test]
+    fn test_paste() {
+        let mut content = Content::from_string("fn a(){}");
+        for _ in 0..7 {
+            content.cursor_right();
+        }
+        content.paste(
+            "let x = 1;\r\n    return x + 1",
+            &Prettyplease,
+            DEFAULT_MAX_WIDTH,
+        );
+        assert_eq!(&content.get_string(), "fn a(){let x=1;return x+1}");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            &content.get_string(),
+            "fn a() {\n    let x = 1;\n    return x + 1\n}\n"
+        );
+        content.undo();
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(&content.get_string(), "fn a() {}\n");
+    }
+
+    #[test]
+    fn test_paste_keeps_blank_lines() {
+        let mut content = Content::from_string("");
+        content.paste("fn a() {}\n\nfn b() {}\n", &Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(&content.typed_string(), "fn a(){}\n\nfn b(){}");
+        content.update_virtual_whitespace(&Prettyplease, DEFAULT_MAX_WIDTH);
+        assert_eq!(&content.get_string(), "fn a() {}\n\nfn b() {}\n");
+    }
+
     #[test]
     fn test_backspace() {
         let mut content = Content::from_string("a \n  def");
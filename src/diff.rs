@@ -0,0 +1,115 @@
+// Longest common subsequence of two sequences, computed using Myers' diff algorithm
+// ("An O(ND) Difference Algorithm and Its Variations"). Returns the pairs of matching indices in
+// increasing order.
+pub fn matches<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    // common prefixes and suffixes are cheap to match and keep the edit graph small
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut res: Vec<_> = (0..prefix).map(|i| (i, i)).collect();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    res.extend(
+        myers(a_mid, b_mid)
+            .into_iter()
+            .map(|(i, j)| (i + prefix, j + prefix)),
+    );
+    res.extend((0..suffix).map(|k| (a.len() - suffix + k, b.len() - suffix + k)));
+    res
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    // v[k + max] is the furthest x reached on diagonal k
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    // trace[d] contains the diagonals -d..=d of v before step d
+    let mut trace = vec![];
+
+    let idx = |k: isize| (k + max) as usize;
+    let go_down =
+        |v: &[isize], k: isize, d: isize| k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]);
+
+    'outer: for d in 0..=max {
+        trace.push(v[idx(-d)..=idx(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if go_down(&v, k, d) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'outer;
+            }
+        }
+    }
+
+    // walk back through the edit graph, collecting the diagonal moves
+    let mut res = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v_d) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // `v_d` only contains the diagonals -d..=d
+        let get = |k: isize| v_d[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            let prev_x = get(prev_k);
+            (prev_x, prev_x - prev_k)
+        };
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            res.push((x as usize, y as usize));
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    res.reverse();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lcs(a: &str, b: &str) -> String {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let res = matches(&a, &b);
+        for (i, j) in &res {
+            assert_eq!(a[*i], b[*j]);
+        }
+        assert!(res.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        res.into_iter().map(|(i, _)| a[i]).collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(lcs("abcabba", "cbabac").len(), 4);
+        assert_eq!(lcs("fn(a,b)", "fn(a,b,)"), "fn(a,b)");
+        assert_eq!(lcs("[a,b,c,]", "[a,b,c]"), "[a,b,c]");
+        assert_eq!(lcs("a+b", "a-b"), "ab");
+        assert_eq!(lcs("", "abc"), "");
+        assert_eq!(lcs("abc", ""), "");
+        assert_eq!(lcs("abc", "xyz"), "");
+    }
+}
@@ -0,0 +1,325 @@
+// Code formatters used to compute the virtual whitespace
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::lexer::{self, Syntax, TokenKind};
+
+mod clike;
+mod json;
+mod toml;
+pub use clike::CLike;
+pub use json::Json;
+pub use toml::Toml;
+
+// rustfmt's default
+pub const DEFAULT_MAX_WIDTH: usize = 100;
+
+pub trait Formatter {
+    // Returns the input formatted to lines of at most `max_width` chars where possible, or None if
+    // it can't be formatted (e.g. because of syntax errors). The built-in JSON, TOML and C-like
+    // formatters don't wrap lines and ignore `max_width`.
+    fn format(&self, input: &str, max_width: usize) -> Option<String>;
+
+    // the lexical syntax of the formatted language
+    fn syntax(&self) -> Syntax {
+        Syntax::RUST
+    }
+
+    // Whether the top-level items of a file (see `Content::update_virtual_whitespace_cached`) can
+    // be formatted one by one, with the same result as formatting the whole file. Also enables
+    // formatting the items that parse when the whole file doesn't.
+    fn formats_items(&self) -> bool {
+        false
+    }
+}
+
+// Formats Rust code in-process using syn and prettyplease
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Prettyplease;
+
+// prettyplease has a fixed margin. Smaller widths are emulated by formatting the code nested in
+// modules, which indents it by 4 columns per module. prettyplease leaves at least 60 columns after
+// the indentation, so widths below 60 aren't possible.
+const PRETTYPLEASE_MARGIN: usize = 89;
+const PRETTYPLEASE_MIN_SPACE: usize = 60;
+
+impl Formatter for Prettyplease {
+    fn format(&self, input: &str, max_width: usize) -> Option<String> {
+        FRAGMENTS
+            .iter()
+            .find_map(|fragment| format_fragment(input, fragment, max_width))
+    }
+
+    fn formats_items(&self) -> bool {
+        true
+    }
+}
+
+// A kind of code that can be formatted: the code is wrapped in `before` and `after` to make it
+// parse as a file, and the formatted code starts with `header` and ends with `footer`, which are
+// removed again.
+struct Fragment {
+    before: &'static str,
+    after: &'static str,
+    header: &'static str,
+    footer: &'static str,
+    indented: bool, // whether the formatted code is indented inside of the wrapper
+    // Whether the item is the wrapper. The wrapped code must consist of only the wrapper, otherwise
+    // the input closed the wrapper itself (e.g. `1}fn g(){2`). None for whole files.
+    is_wrapper: Option<fn(&syn::Item) -> bool>,
+}
+
+// Tried in order until one parses, so that snippets and not only whole files can be formatted.
+// Statements include expressions (as the trailing expression of the block).
+const FRAGMENTS: &[Fragment] = &[
+    // a whole file
+    Fragment {
+        before: "",
+        after: "",
+        header: "",
+        footer: "",
+        indented: false,
+        is_wrapper: None,
+    },
+    // statements
+    Fragment {
+        before: "fn f(){",
+        after: "\n}",
+        header: "fn f() {\n",
+        footer: "}\n",
+        indented: true,
+        is_wrapper: Some(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "f")),
+    },
+    // associated items, e.g. the body of an impl block
+    Fragment {
+        before: "impl T{",
+        after: "\n}",
+        header: "impl T {\n",
+        footer: "}\n",
+        indented: true,
+        is_wrapper: Some(|item| matches!(item, syn::Item::Impl(i) if i.trait_.is_none())),
+    },
+    // a type
+    Fragment {
+        before: "type T=",
+        after: ";",
+        header: "type T = ",
+        footer: ";\n",
+        indented: false,
+        is_wrapper: Some(|item| matches!(item, syn::Item::Type(t) if t.ident == "T")),
+    },
+];
+
+fn format_fragment(input: &str, fragment: &Fragment, max_width: usize) -> Option<String> {
+    // the indentation inside of the wrapper is removed, so it doesn't count to the width
+    let indent = if fragment.indented { "    " } else { "" };
+    let levels = std::cmp::min(
+        PRETTYPLEASE_MARGIN.saturating_sub(max_width + indent.len()),
+        PRETTYPLEASE_MARGIN - PRETTYPLEASE_MIN_SPACE,
+    )
+    .div_ceil(4);
+    let wrapped = format!("{}{}{}", fragment.before, input, fragment.after);
+    if let Some(is_wrapper) = fragment.is_wrapper {
+        let file = syn::parse_file(&wrapped).ok()?;
+        if !matches!(file.items.as_slice(), [item] if is_wrapper(item)) {
+            return None;
+        }
+    }
+    let formatted = if levels == 0 {
+        prettify_code(wrapped)?
+    } else {
+        let nested = format!(
+            "{}{}\n{}",
+            "mod m{".repeat(levels),
+            wrapped,
+            "}".repeat(levels)
+        );
+        unnest(&prettify_code(nested)?, levels).or_else(|| prettify_code(wrapped))?
+    };
+
+    let inner = formatted
+        .strip_prefix(fragment.header)?
+        .strip_suffix(fragment.footer)?;
+    let mut out = String::new();
+    for line in inner.lines() {
+        out.push_str(line.strip_prefix(indent).unwrap_or(line));
+        out.push('\n');
+    }
+    Some(out)
+}
+
+// Removes the `levels` modules around the formatted code and their indentation. Returns None if
+// the code doesn't have the expected shape (e.g. if it's empty).
+fn unnest(formatted: &str, levels: usize) -> Option<String> {
+    let lines: Vec<&str> = formatted.lines().collect();
+    if lines.len() < 2 * levels {
+        return None;
+    }
+    let (head, rest) = lines.split_at(levels);
+    let (body, tail) = rest.split_at(rest.len() - levels);
+    for (i, line) in head.iter().enumerate() {
+        if line.trim_start() != "mod m {" || line.len() - line.trim_start().len() != 4 * i {
+            return None;
+        }
+    }
+    if tail.iter().any(|line| line.trim_start() != "}") {
+        return None;
+    }
+
+    let indent = " ".repeat(4 * levels);
+    let mut out = String::new();
+    for line in body {
+        out.push_str(line.strip_prefix(&indent).unwrap_or(line));
+        out.push('\n');
+    }
+    Some(out)
+}
+
+pub fn prettify_code(input: String) -> Option<String> {
+    let file = syn::parse_file(&input).ok()?;
+    let output = prettyplease::unparse(&file);
+    Some(output)
+}
+
+// Formats Rust code by piping it through an external `rustfmt` process. Without `config_path`,
+// rustfmt looks for a `rustfmt.toml` starting in the working directory.
+#[derive(Clone, Debug)]
+pub struct Rustfmt {
+    pub command: PathBuf,
+    pub config_path: Option<PathBuf>,
+    pub edition: String,
+}
+
+impl Default for Rustfmt {
+    fn default() -> Self {
+        Rustfmt {
+            command: PathBuf::from("rustfmt"),
+            config_path: None,
+            edition: "2021".to_string(),
+        }
+    }
+}
+
+impl Formatter for Rustfmt {
+    fn format(&self, input: &str, max_width: usize) -> Option<String> {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(["--emit", "stdout", "--edition", &self.edition, "--config"])
+            .arg(format!("max_width={}", max_width));
+        if let Some(config_path) = &self.config_path {
+            cmd.arg("--config-path").arg(config_path);
+        }
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        // rustfmt only starts writing after reading all of stdin, so this can't deadlock
+        child.stdin.take()?.write_all(input.as_bytes()).ok()?;
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+}
+
+// A token of the code to format, for the in-process formatters
+struct Token {
+    text: String,
+    kind: TokenKind,
+    newlines_before: usize, // number of newlines in the whitespace before the token
+    space_before: bool,     // whether the token is preceded by whitespace
+}
+
+// the tokens of the input, excluding whitespace
+fn tokens(input: &str, syntax: &Syntax) -> Vec<Token> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut res = vec![];
+    let mut whitespace: &[char] = &[];
+    for t in lexer::tokenize(&chars, syntax) {
+        let text = &chars[t.start..t.end];
+        if t.kind == TokenKind::Whitespace {
+            whitespace = text;
+            continue;
+        }
+        res.push(Token {
+            text: text.iter().collect(),
+            kind: t.kind,
+            newlines_before: whitespace.iter().filter(|c| **c == '\n').count(),
+            space_before: !whitespace.is_empty(),
+        });
+        whitespace = &[];
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prettyplease() {
+        assert_eq!(
+            Prettyplease
+                .format("fn a(){1}", DEFAULT_MAX_WIDTH)
+                .as_deref(),
+            Some("fn a() {\n    1\n}\n")
+        );
+        assert_eq!(Prettyplease.format("fn a({", DEFAULT_MAX_WIDTH), None);
+    }
+
+    #[test]
+    fn test_prettyplease_width() {
+        let input = "fn a(){f(aaaaaaaaaa,bbbbbbbbbb,cccccccccc,dddddddddd,eeeeeeeeee)}struct S;";
+        let wide = "fn a() {\n    f(aaaaaaaaaa, bbbbbbbbbb, cccccccccc, dddddddddd, eeeeeeeeee)\n}\nstruct S;\n";
+        let narrow = "fn a() {\n    f(\n        aaaaaaaaaa,\n        bbbbbbbbbb,\n        cccccccccc,\n        dddddddddd,\n        eeeeeeeeee,\n    )\n}\nstruct S;\n";
+        assert_eq!(Prettyplease.format(input, 89).as_deref(), Some(wide));
+        assert_eq!(Prettyplease.format(input, 70).as_deref(), Some(wide));
+        assert_eq!(Prettyplease.format(input, 60).as_deref(), Some(narrow));
+        assert_eq!(Prettyplease.format(input, 20).as_deref(), Some(narrow));
+        assert_eq!(Prettyplease.format("", 60).as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_prettyplease_fragments() {
+        let format = |input| Prettyplease.format(input, DEFAULT_MAX_WIDTH);
+        assert_eq!(
+            format("let x=1;x+1").as_deref(),
+            Some("let x = 1;\nx + 1\n")
+        );
+        assert_eq!(format("a+b*c").as_deref(), Some("a + b * c\n"));
+        assert_eq!(
+            format("fn a(&self)->u8{if x{1}else{2}}const B:u8=1;").as_deref(),
+            Some("fn a(&self) -> u8 {\n    if x { 1 } else { 2 }\n}\nconst B: u8 = 1;\n")
+        );
+        assert_eq!(format("&'a[u8]").as_deref(), Some("&'a [u8]\n"));
+        assert_eq!(
+            Prettyplease.format("let x=f(aaaaaaaaaa,bbbbbbbbbb,cccccccccc,dddddddddd,eeeeeeeeee);", 60).as_deref(),
+            Some("let x = f(\n    aaaaaaaaaa,\n    bbbbbbbbbb,\n    cccccccccc,\n    dddddddddd,\n    eeeeeeeeee,\n);\n")
+        );
+        assert_eq!(format("let x=;"), None);
+        // the input can't close the wrapper
+        assert_eq!(format("1}fn g(){2"), None);
+        assert_eq!(format("x}impl U{fn h(){}"), None);
+        assert_eq!(format("u8;type U=i8"), None);
+    }
+
+    #[test]
+    #[ignore = "requires rustfmt"]
+    fn test_rustfmt() {
+        let rustfmt = Rustfmt::default();
+        assert_eq!(
+            rustfmt.format("fn a(){1}", DEFAULT_MAX_WIDTH).as_deref(),
+            Some("fn a() {\n    1\n}\n")
+        );
+        assert_eq!(rustfmt.format("fn a({", DEFAULT_MAX_WIDTH), None);
+        assert_eq!(
+            rustfmt
+                .format("fn a(){f(aaaaaaaaaa,bbbbbbbbbb)}", 25)
+                .as_deref(),
+            Some("fn a() {\n    f(\n        aaaaaaaaaa,\n        bbbbbbbbbb,\n    )\n}\n")
+        );
+    }
+}
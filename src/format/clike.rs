@@ -0,0 +1,195 @@
+// A simple in-process formatter for C-like languages (C, C++, Java, JavaScript, ...). Braces open
+// indented blocks, statements end lines and binary operators are surrounded by spaces. It doesn't
+// know the grammar of any particular language, so e.g. pointer declarations are spaced like
+// multiplications.
+use super::{tokens, Formatter, Token};
+use crate::lexer::{Syntax, TokenKind};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CLike;
+
+// longest first
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "&=", "|=", "^=",
+];
+// words that can't end an operand, e.g. the `-` in `return -1` is unary
+const KEYWORDS: &[&str] = &["return", "case", "else", "do", "throw", "new", "delete"];
+// keywords followed by a space before `(`
+const CONTROL: &[&str] = &["if", "for", "while", "switch", "catch", "return"];
+
+impl Formatter for CLike {
+    fn format(&self, input: &str, _max_width: usize) -> Option<String> {
+        let tokens = join_operators(tokens(input, &Syntax::C));
+
+        // whether each token ends an operand, so that a following operator is binary
+        let mut operand = vec![false; tokens.len()];
+        for (i, t) in tokens.iter().enumerate() {
+            let after_operand = i > 0 && operand[i - 1];
+            operand[i] = match t.kind {
+                TokenKind::Ident => !KEYWORDS.contains(&t.text.as_str()),
+                TokenKind::Number | TokenKind::Str | TokenKind::Char => true,
+                _ => match t.text.as_str() {
+                    ")" | "]" => true,
+                    "++" | "--" => after_operand, // postfix
+                    _ => false,
+                },
+            };
+        }
+        let unary = |i: usize| is_unary(&tokens[i].text) && (i == 0 || !operand[i - 1]);
+
+        let mut out = String::new();
+        let mut indent = 0usize;
+        let mut parens = 0usize; // depth of parentheses and brackets
+        let mut ternaries = 0; // number of `?` waiting for their `:`
+        let mut newline = false; // whether the next token starts a new line
+        let mut case_label = false; // the current statement is a `case` or `default` label
+        let mut statement_start = true;
+
+        for (i, t) in tokens.iter().enumerate() {
+            let text = t.text.as_str();
+            let prev = if i > 0 {
+                tokens[i - 1].text.as_str()
+            } else {
+                ""
+            };
+
+            if text == "}" {
+                indent = indent.checked_sub(1)?;
+                newline = prev != "{";
+            }
+            let same_line = matches!(text, ";" | "," | ")" | "else" | "while") && prev == "}";
+            let trailing_comment = t.kind == TokenKind::LineComment && t.newlines_before == 0;
+            if newline && (same_line || trailing_comment) && i > 0 {
+                newline = false;
+            }
+
+            if newline {
+                out.push('\n');
+                out.push_str(&"    ".repeat(indent));
+                newline = false;
+            } else if i > 0 {
+                let space = match text {
+                    "(" if CONTROL.contains(&prev) => true,
+                    ")" | "]" | "," | ";" | "." | "->" => false,
+                    "++" | "--" if operand[i] => false,
+                    ":" => ternaries > 0,
+                    "(" | "[" if operand[i - 1] => false,
+                    _ if matches!(prev, "(" | "[" | "." | "->") || unary(i - 1) => false,
+                    _ if is_operator(text) || is_operator(prev) => true,
+                    _ if matches!(prev, "," | ";" | ":" | ")" | "}") => true,
+                    "{" => true,
+                    _ => t.space_before || (is_word(prev) && is_word(text)),
+                };
+                if space {
+                    out.push(' ');
+                }
+            }
+            out.push_str(text);
+
+            if statement_start {
+                case_label = matches!(text, "case" | "default");
+            }
+            statement_start = false;
+            let ends_line = match text {
+                "{" => {
+                    indent += 1;
+                    true
+                }
+                "}" => true,
+                "(" | "[" => {
+                    parens += 1;
+                    false
+                }
+                ")" | "]" => {
+                    parens = parens.checked_sub(1)?;
+                    false
+                }
+                ";" => parens == 0,
+                "?" => {
+                    ternaries += 1;
+                    false
+                }
+                ":" if ternaries > 0 => {
+                    ternaries -= 1;
+                    false
+                }
+                ":" => case_label && parens == 0,
+                _ => t.kind == TokenKind::LineComment,
+            };
+            if ends_line {
+                newline = true;
+                statement_start = true;
+            }
+        }
+
+        if indent != 0 || parens != 0 {
+            return None;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        Some(out)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::C
+    }
+}
+
+// Joins adjacent punctuation tokens to multi-char operators
+fn join_operators(tokens: Vec<Token>) -> Vec<Token> {
+    let mut res: Vec<Token> = vec![];
+    for t in tokens {
+        if let Some(last) = res.last_mut() {
+            let joined = format!("{}{}", last.text, t.text);
+            if t.kind == TokenKind::Punct && !t.space_before && OPERATORS.contains(&joined.as_str())
+            {
+                last.text = joined;
+                continue;
+            }
+        }
+        res.push(t);
+    }
+    res
+}
+
+fn is_unary(s: &str) -> bool {
+    matches!(s, "-" | "+" | "*" | "&" | "!" | "~" | "++" | "--")
+}
+
+fn is_operator(s: &str) -> bool {
+    OPERATORS.contains(&s) || (s.len() == 1 && "=<>+-*/%&|^?".contains(s))
+}
+
+fn is_word(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '"' || c == '\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let input = "#include <a.h>\nint main(int argc,char*s){for(int i=0;i<-argc;i++){if(!x)f(a[i]->b,-1);else{}}switch(x){case 1:return x?1:2;}do{}while(0);}";
+        let out = "#include <a.h>
+int main(int argc, char * s) {
+    for (int i = 0; i < -argc; i++) {
+        if (!x) f(a[i]->b, -1);
+        else {}
+    }
+    switch (x) {
+        case 1:
+        return x ? 1 : 2;
+    }
+    do {} while (0);
+}
+";
+        assert_eq!(CLike.format(input, 80).as_deref(), Some(out));
+        assert_eq!(CLike.format("f(){", 80), None);
+        assert_eq!(CLike.format("f())", 80), None);
+    }
+}
@@ -0,0 +1,73 @@
+// In-process JSON formatter. Objects and arrays are expanded with one entry per line, like
+// `JSON.stringify(value, null, 2)`.
+use super::{tokens, Formatter};
+use crate::lexer::Syntax;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl Formatter for Json {
+    fn format(&self, input: &str, _max_width: usize) -> Option<String> {
+        let tokens = tokens(input, &Syntax::JSON);
+
+        let mut out = String::new();
+        let mut open = vec![];
+        let newline = |out: &mut String, depth: usize| {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        };
+        for (i, token) in tokens.iter().enumerate() {
+            let next = tokens.get(i + 1).map(|t| t.text.as_str());
+            let token = token.text.as_str();
+            match token {
+                "{" | "[" => {
+                    open.push(if token == "{" { "}" } else { "]" });
+                    out.push_str(token);
+                    if next != open.last().copied() {
+                        newline(&mut out, open.len());
+                    }
+                }
+                "}" | "]" => {
+                    if open.pop()? != token {
+                        return None;
+                    }
+                    if !(out.ends_with('{') || out.ends_with('[')) {
+                        newline(&mut out, open.len());
+                    }
+                    out.push_str(token);
+                }
+                "," => {
+                    out.push(',');
+                    newline(&mut out, open.len());
+                }
+                ":" => out.push_str(": "),
+                _ => out.push_str(token),
+            }
+        }
+        if !open.is_empty() {
+            return None;
+        }
+        out.push('\n');
+        Some(out)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::JSON
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            Json.format(r#"{"a":[1,2],"b":{},"c":"x, y"}"#, 80)
+                .as_deref(),
+            Some("{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {},\n  \"c\": \"x, y\"\n}\n")
+        );
+        assert_eq!(Json.format("[1,2}", 80), None);
+        assert_eq!(Json.format("[1,2", 80), None);
+    }
+}
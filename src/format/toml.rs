@@ -0,0 +1,105 @@
+// In-process TOML formatter. Newlines are significant in TOML, so they are kept, only blank lines
+// are collapsed and tables are separated by a blank line. Within lines, `=` is surrounded by
+// spaces, commas are followed by one and inline tables are padded.
+use super::{tokens, Formatter};
+use crate::lexer::{Syntax, TokenKind};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Toml;
+
+impl Formatter for Toml {
+    fn format(&self, input: &str, _max_width: usize) -> Option<String> {
+        let tokens = tokens(input, &Syntax::TOML);
+        let mut out = String::new();
+        let mut depth = 0usize; // of brackets and braces in values
+        let mut header = false; // whether the current line is a table header
+
+        for (i, t) in tokens.iter().enumerate() {
+            let text = t.text.as_str();
+            if t.kind == TokenKind::Str && !is_terminated(text) {
+                return None;
+            }
+
+            if i == 0 || t.newlines_before > 0 {
+                if i > 0 {
+                    out.push('\n');
+                }
+                header = depth == 0 && text == "[";
+                if header && i > 0 {
+                    out.push('\n');
+                }
+                let closing = matches!(text, "]" | "}");
+                let indent = depth - usize::from(closing && depth > 0);
+                out.push_str(&"    ".repeat(indent));
+            } else {
+                let prev = tokens[i - 1].text.as_str();
+                let space = match (prev, text) {
+                    _ if header => false,
+                    ("=", _) | (_, "=") | (",", _) => true,
+                    (_, ",") => false,
+                    ("{", "}") => false,
+                    ("{", _) | (_, "}") => true,
+                    ("[", _) | (_, "]") => false,
+                    _ => t.space_before || t.kind == TokenKind::LineComment,
+                };
+                if space {
+                    out.push(' ');
+                }
+            }
+
+            out.push_str(text);
+            match text {
+                "[" | "{" if !header => depth += 1,
+                "]" | "}" if !header => depth = depth.checked_sub(1)?,
+                _ => (),
+            }
+        }
+
+        if depth != 0 {
+            return None;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        Some(out)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::TOML
+    }
+}
+
+fn is_terminated(s: &str) -> bool {
+    let quote = s.chars().next().unwrap_or('"');
+    if s.chars().count() < 2 || !s.ends_with(quote) {
+        return false;
+    }
+    // literal strings don't have escapes, the quote of a basic string is escaped by an odd number
+    // of backslashes
+    let backslashes = s[..s.len() - 1]
+        .chars()
+        .rev()
+        .take_while(|c| *c == '\\')
+        .count();
+    quote == '\'' || backslashes % 2 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let input = "a.b=1 # c\n\n\n[t]\nd=[1,2]\ne={x=1,y='s'}\n[[u]]\nf=[\n1,\n]";
+        let out =
+            "a.b = 1 # c\n\n[t]\nd = [1, 2]\ne = { x = 1, y = 's' }\n\n[[u]]\nf = [\n    1,\n]\n";
+        assert_eq!(Toml.format(input, 80).as_deref(), Some(out));
+        assert_eq!(Toml.format("a=[1", 80), None);
+        assert_eq!(Toml.format("a=\"b", 80), None);
+        assert_eq!(Toml.format("a=\"b\\\"", 80), None);
+        assert_eq!(
+            Toml.format("a=\"b\\\\\"", 80).as_deref(),
+            Some("a = \"b\\\\\"\n")
+        );
+    }
+}
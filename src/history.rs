@@ -0,0 +1,141 @@
+// Undo/redo history of edits to the typed text. Virtual whitespace is derived from the typed text,
+// so it isn't recorded.
+
+// Replacement of `deleted` by `inserted` at `offset` (char index into the typed text)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub offset: usize,
+    pub deleted: String,
+    pub inserted: String,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    undo: Vec<(Edit, u64)>, // with the version of the text after the edit
+    redo: Vec<(Edit, u64)>,
+    grouping: bool,    // whether the next edit may be merged into the last one
+    last_version: u64, // the last version that was handed out
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit) {
+        self.redo.clear();
+        let ends_group = edit.inserted.contains('\n');
+        self.last_version += 1;
+
+        let merged = self.grouping
+            && self
+                .undo
+                .last_mut()
+                .is_some_and(|(last, _)| merge(last, &edit));
+        if merged {
+            self.undo.last_mut().unwrap().1 = self.last_version;
+        } else {
+            self.undo.push((edit, self.last_version));
+        }
+        self.grouping = !ends_group;
+    }
+
+    pub fn undo(&mut self) -> Option<Edit> {
+        let (edit, version) = self.undo.pop()?;
+        self.redo.push((edit.clone(), version));
+        self.grouping = false;
+        Some(edit)
+    }
+
+    pub fn redo(&mut self) -> Option<Edit> {
+        let (edit, version) = self.redo.pop()?;
+        self.undo.push((edit.clone(), version));
+        self.grouping = false;
+        Some(edit)
+    }
+
+    // Identifies the current text: the version changes with every edit and returns to an earlier
+    // value when the edits since then are undone. The initial text has version 0.
+    pub fn version(&self) -> u64 {
+        self.undo.last().map_or(0, |(_, version)| *version)
+    }
+}
+
+// Merges consecutive typing, backspaces or deletes into a single undo step. Returns false if the
+// edits can't be merged.
+fn merge(last: &mut Edit, edit: &Edit) -> bool {
+    let len = |s: &str| s.chars().count();
+
+    if edit.deleted.is_empty() && last.offset + len(&last.inserted) == edit.offset {
+        // typing (possibly replacing a selection)
+        last.inserted.push_str(&edit.inserted);
+    } else if !last.inserted.is_empty() || !edit.inserted.is_empty() {
+        return false;
+    } else if edit.offset + len(&edit.deleted) == last.offset {
+        // backspace
+        last.deleted.insert_str(0, &edit.deleted);
+        last.offset = edit.offset;
+    } else if edit.offset == last.offset {
+        // delete
+        last.deleted.push_str(&edit.deleted);
+    } else {
+        return false;
+    }
+    last.cursor_after = edit.cursor_after;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(offset: usize, deleted: &str, inserted: &str) -> Edit {
+        Edit {
+            offset,
+            deleted: deleted.to_string(),
+            inserted: inserted.to_string(),
+            cursor_before: (0, 0),
+            cursor_after: (0, 0),
+        }
+    }
+
+    #[test]
+    fn test_grouping() {
+        let mut history = History::default();
+        history.record(edit(0, "", "a"));
+        history.record(edit(1, "", "b"));
+        history.record(edit(2, "", "\n"));
+        history.record(edit(3, "", "c"));
+        history.record(edit(2, "\nc", ""));
+        history.record(edit(1, "b", ""));
+        assert_eq!(history.undo(), Some(edit(1, "b\nc", "")));
+        assert_eq!(history.undo(), Some(edit(3, "", "c")));
+        assert_eq!(history.undo(), Some(edit(0, "", "ab\n")));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(edit(0, "", "ab\n")));
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut history = History::default();
+        history.record(edit(0, "", "a"));
+        history.undo();
+        history.record(edit(0, "", "b"));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn test_version() {
+        let mut history = History::default();
+        history.record(edit(0, "", "a"));
+        let a = history.version();
+        history.record(edit(1, "", "b"));
+        assert_ne!(history.version(), a);
+        history.undo();
+        assert_eq!(history.version(), 0);
+        history.redo();
+        let ab = history.version();
+        assert_ne!(ab, 0);
+        history.undo();
+        history.record(edit(0, "", "c"));
+        assert_ne!(history.version(), ab);
+    }
+}
@@ -0,0 +1,202 @@
+// Key bindings shared by the frontends. Clipboard shortcuts are handled by the frontends, as they
+// depend on the platform.
+use crate::content::Content;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Backspace,
+    Delete,
+    Enter,
+    Char(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyPress {
+    // AltGr is reported as ctrl + alt on Windows
+    fn is_shortcut(self) -> bool {
+        self.ctrl && !self.alt
+    }
+}
+
+// What a key press changed, so frontends know what to update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    None,
+    CursorMoved,
+    ContentChanged,
+}
+
+// When frontends update the virtual whitespace after the content changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoUpdate {
+    Off,        // only on request
+    Always,     // after every change
+    Idle,       // once the content hasn't changed for a while
+    Boundaries, // after typing a newline, `;` or `}`, not in the middle of a word
+}
+
+impl AutoUpdate {
+    pub const ALL: [AutoUpdate; 4] = [
+        AutoUpdate::Off,
+        AutoUpdate::Always,
+        AutoUpdate::Idle,
+        AutoUpdate::Boundaries,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AutoUpdate::Off => "off",
+            AutoUpdate::Always => "on every change",
+            AutoUpdate::Idle => "when idle",
+            AutoUpdate::Boundaries => "on newline, ; and }",
+        }
+    }
+
+    // Whether a change by `press` (None for other changes, e.g. pasting) updates the virtual
+    // whitespace right away. In `Idle` mode, frontends update it after a delay instead.
+    pub fn updates_after(self, press: Option<KeyPress>) -> bool {
+        match self {
+            AutoUpdate::Off | AutoUpdate::Idle => false,
+            AutoUpdate::Always => true,
+            AutoUpdate::Boundaries => match press.map(|p| (p.key, p.is_shortcut())) {
+                // ctrl+z and ctrl+shift+z
+                Some((Key::Char(c), shortcut)) => matches!(c, ';' | '}') || shortcut,
+                Some((Key::Backspace | Key::Delete, _)) => false,
+                _ => true,
+            },
+        }
+    }
+}
+
+pub fn handle_key(content: &mut Content, press: KeyPress) -> KeyAction {
+    let movement: fn(&mut Content) = match press.key {
+        Key::Left => Content::cursor_left,
+        Key::Right => Content::cursor_right,
+        Key::Up => Content::cursor_up,
+        Key::Down => Content::cursor_down,
+        Key::Home => Content::cursor_home,
+        Key::End => Content::cursor_end,
+        Key::Char('a') if press.is_shortcut() => {
+            content.select_all();
+            return KeyAction::CursorMoved;
+        }
+        Key::Char('z') | Key::Char('Z') if press.is_shortcut() => {
+            let changed = if press.shift {
+                content.redo()
+            } else {
+                content.undo()
+            };
+            return if changed {
+                KeyAction::ContentChanged
+            } else {
+                KeyAction::None
+            };
+        }
+        Key::Backspace => return edit(content, Content::backspace),
+        Key::Delete => return edit(content, Content::delete),
+        Key::Enter => return edit(content, |c| c.insert('\n')),
+        // unbound shortcuts don't insert anything
+        Key::Char(_) if press.is_shortcut() => return KeyAction::None,
+        Key::Char(ch) => return edit(content, |c| c.insert(ch)),
+    };
+    content.set_selecting(press.shift);
+    movement(content);
+    KeyAction::CursorMoved
+}
+
+fn edit(content: &mut Content, f: impl FnOnce(&mut Content)) -> KeyAction {
+    f(content);
+    KeyAction::ContentChanged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::GetString;
+
+    fn press(content: &mut Content, key: Key, ctrl: bool, shift: bool) -> KeyAction {
+        handle_key(
+            content,
+            KeyPress {
+                key,
+                ctrl,
+                shift,
+                alt: false,
+            },
+        )
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut c = Content::from_string("ab");
+        assert_eq!(
+            press(&mut c, Key::End, false, false),
+            KeyAction::CursorMoved
+        );
+        assert_eq!(
+            press(&mut c, Key::Left, false, true),
+            KeyAction::CursorMoved
+        );
+        assert_eq!(
+            press(&mut c, Key::Char('x'), false, false),
+            KeyAction::ContentChanged
+        );
+        assert_eq!(c.get_string(), "ax");
+        assert_eq!(
+            press(&mut c, Key::Char('z'), true, false),
+            KeyAction::ContentChanged
+        );
+        assert_eq!(c.get_string(), "ab");
+        assert_eq!(
+            press(&mut c, Key::Char('Z'), true, true),
+            KeyAction::ContentChanged
+        );
+        assert_eq!(press(&mut c, Key::Char('Z'), true, true), KeyAction::None);
+        assert_eq!(c.get_string(), "ax");
+        assert_eq!(press(&mut c, Key::Char('w'), true, false), KeyAction::None);
+        assert_eq!(c.get_string(), "ax");
+        let alt_gr = KeyPress {
+            key: Key::Char('{'),
+            ctrl: true,
+            shift: false,
+            alt: true,
+        };
+        assert_eq!(handle_key(&mut c, alt_gr), KeyAction::ContentChanged);
+        assert_eq!(c.get_string(), "ax{");
+    }
+
+    #[test]
+    fn test_auto_update() {
+        let press = |key, ctrl| {
+            Some(KeyPress {
+                key,
+                ctrl,
+                shift: false,
+                alt: false,
+            })
+        };
+        let boundaries = AutoUpdate::Boundaries;
+        assert!(!boundaries.updates_after(press(Key::Char('a'), false)));
+        assert!(!boundaries.updates_after(press(Key::Backspace, false)));
+        assert!(boundaries.updates_after(press(Key::Char(';'), false)));
+        assert!(boundaries.updates_after(press(Key::Char('}'), false)));
+        assert!(boundaries.updates_after(press(Key::Enter, false)));
+        assert!(boundaries.updates_after(press(Key::Char('z'), true)));
+        assert!(boundaries.updates_after(None));
+        assert!(AutoUpdate::Always.updates_after(press(Key::Char('a'), false)));
+        assert!(!AutoUpdate::Idle.updates_after(None));
+    }
+}
@@ -0,0 +1,99 @@
+// Registry of the supported languages, used to pick the formatter of a file by its extension
+use std::path::Path;
+
+use crate::format::{CLike, Formatter, Json, Prettyplease, Toml};
+
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub formatter: fn() -> Box<dyn Formatter>,
+}
+
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        formatter: || Box::new(Prettyplease),
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        formatter: || Box::new(Json),
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        formatter: || Box::new(Toml),
+    },
+    Language {
+        name: "C-like",
+        extensions: &[
+            "c", "h", "cc", "cpp", "hpp", "cs", "java", "js", "ts", "go", "kt",
+        ],
+        formatter: || Box::new(CLike),
+    },
+];
+
+// The default language
+pub static RUST: &Language = &LANGUAGES[0];
+
+// Languages are compared by identity, there is one entry per language in `LANGUAGES`
+impl PartialEq for Language {
+    fn eq(&self, other: &Language) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Returns the language of a file with the given path (or name). Files with an unknown extension
+// are treated as Rust.
+pub fn for_path(path: impl AsRef<Path>) -> &'static Language {
+    let ext = path.as_ref().extension().and_then(|e| e.to_str());
+    let ext = ext.map(str::to_ascii_lowercase);
+    LANGUAGES
+        .iter()
+        .find(|l| ext.as_deref().is_some_and(|e| l.extensions.contains(&e)))
+        .unwrap_or(RUST)
+}
+
+pub fn by_name(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{Content, GetString};
+
+    #[test]
+    fn test_for_path() {
+        assert_eq!(for_path("src/lib.rs").name, "Rust");
+        assert_eq!(for_path("Cargo.toml").name, "TOML");
+        assert_eq!(for_path("a/b.JSON").name, "JSON");
+        assert_eq!(for_path("main.c").name, "C-like");
+        assert_eq!(for_path("README").name, "Rust");
+        assert_eq!(by_name("TOML").map(|l| l.extensions), Some(&["toml"][..]));
+        assert!(for_path("README") == RUST);
+        assert!(for_path("a.json") != RUST);
+    }
+
+    #[test]
+    fn test_json_content() {
+        let formatter = (for_path("a.json").formatter)();
+        let mut content = Content::from_formatted(
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}\n",
+            formatter.as_ref(),
+            80,
+        );
+        assert_eq!(content.typed_string(), "{\"a\":[1,2]}");
+        assert_eq!(
+            content.get_string(),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}\n"
+        );
+        content.cursor_to((0, 1));
+        for c in "\"b\":0,".chars() {
+            content.insert(c);
+        }
+        content.update_virtual_whitespace(formatter.as_ref(), 80);
+        assert_eq!(content.typed_string(), "{\"b\":0,\"a\":[1,2]}");
+    }
+}
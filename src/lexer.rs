@@ -0,0 +1,519 @@
+// A small, error tolerant lexer for Rust and languages with a similar lexical structure (see
+// `Syntax`). It's used to find comments and literals in the typed text, so it doesn't need to
+// validate anything. Unterminated literals and comments simply extend to the end of the input.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    Ident,
+    Lifetime,
+    Char,
+    Str,
+    Number,
+    Punct,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize, // index of the first character
+    pub end: usize,   // index after the last character
+}
+
+// The lexical differences between the supported languages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Syntax {
+    pub line_comment: &'static str, // empty if the language doesn't have line comments
+    pub block_comments: bool,       // `/* */`
+    pub directives: bool,           // lines starting with `#` are lexed as line comments (C)
+    pub rust_literals: bool,        // raw and byte strings, lifetimes
+    pub single_quoted_strings: bool,
+    pub significant_newlines: bool, // newlines can't be replaced by virtual whitespace
+    pub joined: &'static [&'static str], // operators that need a separator when split
+    pub keywords: &'static [&'static str], // highlighted identifiers
+}
+
+// Syntax highlighting class of a character
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Highlight {
+    Keyword,
+    Literal,
+    Lifetime,
+    Comment,
+    Macro, // including the `!`
+}
+
+const RUST_JOINED: &[&str] = &[
+    "::", "->", "<-", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "^=",
+    "&=", "|=", "<<", ">>", "..", ".=", "//", "/*", "*/",
+];
+const C_JOINED: &[&str] = &[
+    "->", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<",
+    ">>", "++", "--", "//", "/*", "*/",
+];
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+const C_KEYWORDS: &[&str] = &[
+    "break", "case", "char", "class", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "false", "float", "for", "function", "if", "int", "long", "new", "null",
+    "private", "public", "return", "short", "signed", "static", "struct", "switch", "this", "true",
+    "typedef", "union", "unsigned", "var", "void", "while",
+];
+
+impl Syntax {
+    pub const RUST: Syntax = Syntax {
+        line_comment: "//",
+        block_comments: true,
+        directives: false,
+        rust_literals: true,
+        single_quoted_strings: false,
+        significant_newlines: false,
+        joined: RUST_JOINED,
+        keywords: RUST_KEYWORDS,
+    };
+    pub const C: Syntax = Syntax {
+        directives: true,
+        rust_literals: false,
+        joined: C_JOINED,
+        keywords: C_KEYWORDS,
+        ..Syntax::RUST
+    };
+    pub const JSON: Syntax = Syntax {
+        line_comment: "",
+        block_comments: false,
+        directives: false,
+        rust_literals: false,
+        single_quoted_strings: false,
+        significant_newlines: false,
+        joined: &[],
+        keywords: &["true", "false", "null"],
+    };
+    pub const TOML: Syntax = Syntax {
+        line_comment: "#",
+        single_quoted_strings: true,
+        significant_newlines: true,
+        ..Syntax::JSON
+    };
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Syntax::RUST
+    }
+}
+
+impl TokenKind {
+    pub fn is_literal(self) -> bool {
+        matches!(self, TokenKind::Char | TokenKind::Str)
+    }
+}
+
+pub fn tokenize(chars: &[char], syntax: &Syntax) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let (kind, end) = next_token(chars, i, syntax);
+        tokens.push(Token {
+            kind,
+            start: i,
+            end,
+        });
+        i = end;
+    }
+    tokens
+}
+
+// The highlighting of every char. Identifiers directly followed by `!` are highlighted as macros.
+pub fn highlight(chars: &[char], syntax: &Syntax) -> Vec<Option<Highlight>> {
+    let mut highlights = vec![None; chars.len()];
+    for token in tokenize(chars, syntax) {
+        let text: String = chars[token.start..token.end].iter().collect();
+        let is_macro =
+            || chars.get(token.end) == Some(&'!') && chars.get(token.end + 1) != Some(&'=');
+        let (highlight, end) = match token.kind {
+            TokenKind::LineComment | TokenKind::BlockComment => (Highlight::Comment, token.end),
+            TokenKind::Char | TokenKind::Str | TokenKind::Number => (Highlight::Literal, token.end),
+            TokenKind::Lifetime => (Highlight::Lifetime, token.end),
+            TokenKind::Ident if syntax.keywords.contains(&text.as_str()) => {
+                (Highlight::Keyword, token.end)
+            }
+            TokenKind::Ident if is_macro() => (Highlight::Macro, token.end + 1),
+            _ => continue,
+        };
+        for h in &mut highlights[token.start..end] {
+            *h = Some(highlight);
+        }
+    }
+    highlights
+}
+
+// Removes all whitespace that isn't needed to separate tokens, so that it can be displayed as
+// virtual whitespace instead. Whitespace in literals and comments and the newlines ending line
+// comments (or any newlines, if they are significant) are kept.
+pub fn minimize_whitespace(input: &str, syntax: &Syntax) -> String {
+    let chars: Vec<char> = input.chars().filter(|c| *c != '\r').collect();
+    let tokens = tokenize(&chars, syntax);
+    let mut out = String::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Whitespace {
+            out.extend(&chars[token.start..token.end]);
+            continue;
+        }
+        let (Some(prev), Some(next)) = (i.checked_sub(1).map(|i| &tokens[i]), tokens.get(i + 1))
+        else {
+            continue;
+        };
+        let newline = chars[token.start..token.end].contains(&'\n');
+        if prev.kind == TokenKind::LineComment || (newline && syntax.significant_newlines) {
+            out.push('\n');
+        } else if needs_separator(chars[prev.end - 1], chars[next.start], syntax) {
+            out.push(' ');
+        }
+    }
+    out
+}
+
+// whether the chars `a` and `b` would be lexed differently when written without a space between
+fn needs_separator(a: char, b: char, syntax: &Syntax) -> bool {
+    if is_ident_continue(a) {
+        is_ident_continue(b) || matches!(b, '\'' | '"' | '#') || (a.is_ascii_digit() && b == '.')
+    } else {
+        syntax
+            .joined
+            .iter()
+            .any(|j| j.starts_with(a) && j.ends_with(b))
+    }
+}
+
+fn next_token(chars: &[char], i: usize, syntax: &Syntax) -> (TokenKind, usize) {
+    let at = |j: usize| chars.get(j).copied().unwrap_or('\0');
+    let c = chars[i];
+
+    if c.is_whitespace() {
+        let end = skip_while(chars, i, |c| c.is_whitespace());
+        return (TokenKind::Whitespace, end);
+    }
+
+    let starts_with = |s: &str| !s.is_empty() && s.chars().enumerate().all(|(j, c)| at(i + j) == c);
+    let line_start = chars[..i]
+        .iter()
+        .rev()
+        .find(|c| !matches!(c, ' ' | '\t'))
+        .is_none_or(|c| *c == '\n');
+    if starts_with(syntax.line_comment) || (syntax.directives && c == '#' && line_start) {
+        let end = skip_while(chars, i, |c| c != '\n');
+        return (TokenKind::LineComment, end);
+    }
+    if syntax.block_comments && starts_with("/*") {
+        return (TokenKind::BlockComment, block_comment_end(chars, i));
+    }
+    if !syntax.rust_literals {
+        return match c {
+            '"' => (TokenKind::Str, quoted_end(chars, i, '"')),
+            '\'' if syntax.single_quoted_strings => (TokenKind::Str, literal_string_end(chars, i)),
+            '\'' => (TokenKind::Char, quoted_end(chars, i, '\'')),
+            _ => simple_token(chars, i),
+        };
+    }
+
+    match (c, at(i + 1)) {
+        ('b', '\'') => return (TokenKind::Char, quoted_end(chars, i + 1, '\'')),
+        ('b', '"') | ('c', '"') => return (TokenKind::Str, quoted_end(chars, i + 1, '"')),
+        ('b', 'r') | ('c', 'r') if matches!(at(i + 2), '"' | '#') => {
+            if let Some(end) = raw_string_end(chars, i + 1) {
+                return (TokenKind::Str, end);
+            }
+        }
+        ('r', '"') | ('r', '#') => {
+            if let Some(end) = raw_string_end(chars, i) {
+                return (TokenKind::Str, end);
+            }
+            if is_ident_start(at(i + 2)) {
+                // raw identifier
+                return (
+                    TokenKind::Ident,
+                    skip_while(chars, i + 2, is_ident_continue),
+                );
+            }
+        }
+        ('"', _) => return (TokenKind::Str, quoted_end(chars, i, '"')),
+        ('\'', '\\') => return (TokenKind::Char, quoted_end(chars, i, '\'')),
+        ('\'', _) if at(i + 2) == '\'' => return (TokenKind::Char, i + 3),
+        ('\'', n) if is_ident_start(n) => {
+            return (
+                TokenKind::Lifetime,
+                skip_while(chars, i + 1, is_ident_continue),
+            )
+        }
+        _ => (),
+    }
+    simple_token(chars, i)
+}
+
+// identifiers, numbers and punctuation
+fn simple_token(chars: &[char], i: usize) -> (TokenKind, usize) {
+    let c = chars[i];
+    if is_ident_start(c) {
+        (TokenKind::Ident, skip_while(chars, i, is_ident_continue))
+    } else if c.is_ascii_digit() {
+        (TokenKind::Number, number_end(chars, i))
+    } else {
+        (TokenKind::Punct, i + 1)
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn skip_while(chars: &[char], mut i: usize, f: impl Fn(char) -> bool) -> usize {
+    while i < chars.len() && f(chars[i]) {
+        i += 1;
+    }
+    i
+}
+
+// `i` points to the opening quote
+fn quoted_end(chars: &[char], mut i: usize, quote: char) -> usize {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+// `i` points to the `r`
+fn raw_string_end(chars: &[char], i: usize) -> Option<usize> {
+    let hashes = skip_while(chars, i + 1, |c| c == '#') - (i + 1);
+    if chars.get(i + 1 + hashes) != Some(&'"') {
+        return None;
+    }
+    let mut j = i + 2 + hashes;
+    while j < chars.len() {
+        if chars[j] == '"'
+            && chars[j + 1..]
+                .iter()
+                .take(hashes)
+                .filter(|c| **c == '#')
+                .count()
+                == hashes
+        {
+            return Some(j + 1 + hashes);
+        }
+        j += 1;
+    }
+    Some(chars.len())
+}
+
+// TOML literal strings don't have escapes
+fn literal_string_end(chars: &[char], i: usize) -> usize {
+    chars[i + 1..]
+        .iter()
+        .position(|c| *c == '\'')
+        .map_or(chars.len(), |j| i + j + 2)
+}
+
+fn block_comment_end(chars: &[char], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('/', Some('*')) => {
+                depth += 1;
+                i += 2;
+            }
+            ('*', Some('/')) => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+fn number_end(chars: &[char], mut i: usize) -> usize {
+    let hex = chars.get(i + 1).is_some_and(|c| matches!(c, 'x' | 'X'));
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied().unwrap_or('\0');
+        if is_ident_continue(c) {
+            i += 1;
+        } else if c == '.' && next.is_ascii_digit() {
+            // `1.5`, but not `1..5` or `1.max(2)`
+            i += 1;
+        } else if matches!(c, '+' | '-')
+            && !hex
+            && matches!(chars[i - 1], 'e' | 'E')
+            && next.is_ascii_digit()
+        {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, String)> {
+        kinds_with(input, &Syntax::RUST)
+    }
+
+    fn kinds_with(input: &str, syntax: &Syntax) -> Vec<(TokenKind, String)> {
+        let chars: Vec<char> = input.chars().collect();
+        tokenize(&chars, syntax)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, chars[t.start..t.end].iter().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_comments() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("a// b\n/* c /* d */ */e"),
+            vec![
+                (Ident, "a".to_string()),
+                (LineComment, "// b".to_string()),
+                (BlockComment, "/* c /* d */ */".to_string()),
+                (Ident, "e".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds(r##"f("a\"//",'"',b'\'',r#"x"y"#,'a,1.5e-3)"##),
+            vec![
+                (Ident, "f".to_string()),
+                (Punct, "(".to_string()),
+                (Str, r#""a\"//""#.to_string()),
+                (Punct, ",".to_string()),
+                (Char, "'\"'".to_string()),
+                (Punct, ",".to_string()),
+                (Char, r"b'\''".to_string()),
+                (Punct, ",".to_string()),
+                (Str, r##"r#"x"y"#"##.to_string()),
+                (Punct, ",".to_string()),
+                (Lifetime, "'a".to_string()),
+                (Punct, ",".to_string()),
+                (Number, "1.5e-3".to_string()),
+                (Punct, ")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minimize_whitespace() {
+        assert_eq!(
+            minimize_whitespace(
+                "pub fn a(x: &mut u8) -> u8 {\n    let y = - -1; // c d\n    /* e */ r#\"a  b\"#\n}\n",
+                &Syntax::RUST
+            ),
+            "pub fn a(x:&mut u8)->u8{let y=--1;// c d\n/* e */r#\"a  b\"#}"
+        );
+        assert_eq!(
+            minimize_whitespace("a < -1 .. = 1 .max(b' ')", &Syntax::RUST),
+            "a< -1 .. =1 .max(b' ')"
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        use Highlight::*;
+        let input: Vec<char> = "fn f<'a>(){x!(1);a!=b;y !();// c\n}".chars().collect();
+        let highlights = highlight(&input, &Syntax::RUST);
+        let runs: Vec<(String, Option<Highlight>)> =
+            input
+                .iter()
+                .zip(&highlights)
+                .fold(vec![], |mut runs: Vec<(String, _)>, (c, h)| {
+                    match runs.last_mut() {
+                        Some((s, last)) if last == h => s.push(*c),
+                        _ => runs.push((c.to_string(), *h)),
+                    }
+                    runs
+                });
+        assert_eq!(
+            runs,
+            vec![
+                ("fn".to_string(), Some(Keyword)),
+                (" f<".to_string(), None),
+                ("'a".to_string(), Some(Lifetime)),
+                (">(){".to_string(), None),
+                ("x!".to_string(), Some(Macro)),
+                ("(".to_string(), None),
+                ("1".to_string(), Some(Literal)),
+                (");a!=b;y !();".to_string(), None),
+                ("// c".to_string(), Some(Comment)),
+                ("\n}".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("x/* a \"b"),
+            vec![
+                (Ident, "x".to_string()),
+                (BlockComment, "/* a \"b".to_string())
+            ]
+        );
+        assert_eq!(kinds("\"abc"), vec![(Str, "\"abc".to_string())]);
+    }
+
+    #[test]
+    fn test_other_syntaxes() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds_with("a='b\\' # c\n", &Syntax::TOML),
+            vec![
+                (Ident, "a".to_string()),
+                (Punct, "=".to_string()),
+                (Str, "'b\\'".to_string()),
+                (LineComment, "# c".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds_with("#include <a>\nx#y", &Syntax::C)[..2],
+            [
+                (LineComment, "#include <a>".to_string()),
+                (Ident, "x".to_string()),
+            ]
+        );
+        assert_eq!(
+            minimize_whitespace("a = 1\n\n[b]\nc = 'x  y'\n", &Syntax::TOML),
+            "a=1\n[b]\nc='x  y'"
+        );
+        assert_eq!(
+            minimize_whitespace("i = a - -b;\n#define X 1\nj++;", &Syntax::C),
+            "i=a- -b;#define X 1\nj++;"
+        );
+    }
+}
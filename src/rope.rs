@@ -0,0 +1,410 @@
+// A sequence stored in a balanced tree of chunks (a rope). Every node caches the number and the
+// summary of its elements, so that elements can be found by index or by their summary (e.g. their
+// visual position) and inserted or removed in O(log n), without moving the rest of the sequence.
+use std::fmt::Debug;
+use std::ops::{Add, Index, Range};
+
+const MAX_LEAF: usize = 64;
+const MAX_CHILDREN: usize = 16;
+
+pub trait Summarize {
+    type Summary: Copy + Debug + Default + Add<Output = Self::Summary>;
+
+    fn summarize(&self) -> Self::Summary;
+}
+
+#[derive(Clone, Debug)]
+pub struct Rope<T: Summarize> {
+    root: Node<T>,
+}
+
+// Nodes aren't merged when elements are removed, only empty nodes are dropped. The height of the
+// tree stays logarithmic in the largest number of elements it held.
+#[derive(Clone, Debug)]
+struct Node<T: Summarize> {
+    len: usize,
+    summary: T::Summary,
+    children: Children<T>,
+}
+
+#[derive(Clone, Debug)]
+enum Children<T: Summarize> {
+    Leaf(Vec<T>),
+    Internal(Vec<Node<T>>),
+}
+
+impl<T: Summarize> Node<T> {
+    fn new(children: Children<T>) -> Node<T> {
+        let mut node = Node {
+            len: 0,
+            summary: Default::default(),
+            children,
+        };
+        node.refresh();
+        node
+    }
+
+    fn refresh(&mut self) {
+        let (len, summary) = match &self.children {
+            Children::Leaf(items) => (
+                items.len(),
+                items
+                    .iter()
+                    .fold(Default::default(), |s, x| s + x.summarize()),
+            ),
+            Children::Internal(nodes) => (
+                nodes.iter().map(|n| n.len).sum(),
+                nodes.iter().fold(Default::default(), |s, n| s + n.summary),
+            ),
+        };
+        self.len = len;
+        self.summary = summary;
+    }
+
+    fn get(&self, i: usize) -> &T {
+        match &self.children {
+            Children::Leaf(items) => &items[i],
+            Children::Internal(nodes) => {
+                let (c, j) = child_at(nodes, i);
+                nodes[c].get(j)
+            }
+        }
+    }
+
+    fn update<R>(&mut self, i: usize, f: impl FnOnce(&mut T) -> R) -> R {
+        let res = match &mut self.children {
+            Children::Leaf(items) => f(&mut items[i]),
+            Children::Internal(nodes) => {
+                let (c, j) = child_at(nodes, i);
+                nodes[c].update(j, f)
+            }
+        };
+        self.refresh();
+        res
+    }
+
+    // calls `f` for the elements in `range` (relative to this node)
+    fn update_range(&mut self, range: Range<usize>, f: &mut impl FnMut(&mut T)) {
+        match &mut self.children {
+            Children::Leaf(items) => items[range].iter_mut().for_each(f),
+            Children::Internal(nodes) => {
+                let mut start = 0;
+                for node in nodes {
+                    let end = start + node.len;
+                    if start < range.end && range.start < end {
+                        let from = range.start.saturating_sub(start);
+                        let to = std::cmp::min(range.end, end) - start;
+                        node.update_range(from..to, f);
+                    }
+                    start = end;
+                }
+            }
+        }
+        self.refresh();
+    }
+
+    // Inserts `x` at index `i` and returns the new right sibling if the node had to be split
+    fn insert(&mut self, i: usize, x: T) -> Option<Node<T>> {
+        let split = match &mut self.children {
+            Children::Leaf(items) => {
+                items.insert(i, x);
+                (items.len() > MAX_LEAF)
+                    .then(|| Node::new(Children::Leaf(items.split_off(items.len() / 2))))
+            }
+            Children::Internal(nodes) => {
+                let (c, j) = child_at(nodes, i);
+                if let Some(sibling) = nodes[c].insert(j, x) {
+                    nodes.insert(c + 1, sibling);
+                }
+                (nodes.len() > MAX_CHILDREN)
+                    .then(|| Node::new(Children::Internal(nodes.split_off(nodes.len() / 2))))
+            }
+        };
+        self.refresh();
+        split
+    }
+
+    fn remove(&mut self, i: usize) -> T {
+        let x = match &mut self.children {
+            Children::Leaf(items) => items.remove(i),
+            Children::Internal(nodes) => {
+                let (c, j) = child_at(nodes, i);
+                let x = nodes[c].remove(j);
+                if nodes[c].len == 0 {
+                    nodes.remove(c);
+                }
+                x
+            }
+        };
+        self.refresh();
+        x
+    }
+}
+
+// Index of the child containing element `i` and the index of the element within that child. An
+// index past the end belongs to the last child.
+fn child_at<T: Summarize>(nodes: &[Node<T>], mut i: usize) -> (usize, usize) {
+    for (c, node) in nodes.iter().enumerate() {
+        if i < node.len || c == nodes.len() - 1 {
+            return (c, i);
+        }
+        i -= node.len;
+    }
+    unreachable!("internal nodes have children")
+}
+
+impl<T: Summarize> Rope<T> {
+    pub fn len(&self) -> usize {
+        self.root.len
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.iter_from(0)
+    }
+
+    // iterates over the elements starting at index `start`
+    pub fn iter_from(&self, start: usize) -> Iter<'_, T> {
+        let mut stack = vec![];
+        let mut node = &self.root;
+        let mut i = start;
+        loop {
+            match &node.children {
+                Children::Leaf(items) => {
+                    return Iter {
+                        stack,
+                        leaf: items[i.min(items.len())..].iter(),
+                    };
+                }
+                Children::Internal(nodes) => {
+                    let (c, j) = child_at(nodes, i);
+                    stack.push(nodes[c + 1..].iter());
+                    node = &nodes[c];
+                    i = j;
+                }
+            }
+        }
+    }
+
+    // Calls `f` with a mutable reference to element `i` and updates the summaries
+    pub fn update<R>(&mut self, i: usize, f: impl FnOnce(&mut T) -> R) -> R {
+        assert!(i < self.len(), "index out of bounds");
+        self.root.update(i, f)
+    }
+
+    pub fn update_range(&mut self, range: Range<usize>, mut f: impl FnMut(&mut T)) {
+        assert!(range.end <= self.len(), "range out of bounds");
+        if !range.is_empty() {
+            self.root.update_range(range, &mut f);
+        }
+    }
+
+    // Calls `f` with the elements in `range` as a slice, for algorithms that need one
+    pub fn with_slice<R>(&mut self, range: Range<usize>, f: impl FnOnce(&mut [T]) -> R) -> R
+    where
+        T: Clone,
+    {
+        let mut items: Vec<T> = self
+            .iter_from(range.start)
+            .take(range.len())
+            .cloned()
+            .collect();
+        let res = f(&mut items);
+        let mut items = items.into_iter();
+        self.update_range(range, |x| *x = items.next().unwrap());
+        res
+    }
+
+    pub fn insert(&mut self, i: usize, x: T) {
+        assert!(i <= self.len(), "index out of bounds");
+        if let Some(sibling) = self.root.insert(i, x) {
+            let left = std::mem::replace(&mut self.root, Node::new(Children::Leaf(vec![])));
+            self.root = Node::new(Children::Internal(vec![left, sibling]));
+        }
+    }
+
+    pub fn remove(&mut self, i: usize) -> T {
+        assert!(i < self.len(), "index out of bounds");
+        let x = self.root.remove(i);
+        loop {
+            match &mut self.root.children {
+                Children::Internal(nodes) if nodes.len() <= 1 => {
+                    self.root = nodes.pop().unwrap_or(Node::new(Children::Leaf(vec![])));
+                }
+                _ => return x,
+            }
+        }
+    }
+
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        for _ in range.clone() {
+            self.remove(range.start);
+        }
+    }
+
+    // summary of the elements before index `i`
+    pub fn summary_before(&self, mut i: usize) -> T::Summary {
+        let mut summary = T::Summary::default();
+        let mut node = &self.root;
+        loop {
+            match &node.children {
+                Children::Leaf(items) => {
+                    return items[..i].iter().fold(summary, |s, x| s + x.summarize());
+                }
+                Children::Internal(nodes) => {
+                    let (c, j) = child_at(nodes, i);
+                    summary = nodes[..c].iter().fold(summary, |s, n| s + n.summary);
+                    node = &nodes[c];
+                    i = j;
+                }
+            }
+        }
+    }
+
+    // Returns the index of the first element for which `pred` holds on the summary of the elements
+    // up to and including it, or the length if there's none. `pred` must stay true once it holds.
+    pub fn search(&self, pred: impl Fn(&T::Summary) -> bool) -> usize {
+        let mut summary = T::Summary::default();
+        let mut index = 0;
+        let mut node = &self.root;
+        'outer: loop {
+            match &node.children {
+                Children::Leaf(items) => {
+                    for x in items {
+                        summary = summary + x.summarize();
+                        if pred(&summary) {
+                            return index;
+                        }
+                        index += 1;
+                    }
+                    return self.len();
+                }
+                Children::Internal(nodes) => {
+                    for n in nodes {
+                        if pred(&(summary + n.summary)) {
+                            node = n;
+                            continue 'outer;
+                        }
+                        summary = summary + n.summary;
+                        index += n.len;
+                    }
+                    return self.len();
+                }
+            }
+        }
+    }
+}
+
+impl<T: Summarize> From<Vec<T>> for Rope<T> {
+    fn from(items: Vec<T>) -> Rope<T> {
+        let mut nodes: Vec<Node<T>> = vec![];
+        let mut items = items.into_iter().peekable();
+        while items.peek().is_some() {
+            let chunk = items.by_ref().take(MAX_LEAF).collect();
+            nodes.push(Node::new(Children::Leaf(chunk)));
+        }
+        while nodes.len() > 1 {
+            let mut parents = vec![];
+            let mut children = nodes.into_iter().peekable();
+            while children.peek().is_some() {
+                let chunk = children.by_ref().take(MAX_CHILDREN).collect();
+                parents.push(Node::new(Children::Internal(chunk)));
+            }
+            nodes = parents;
+        }
+        Rope {
+            root: nodes
+                .pop()
+                .unwrap_or_else(|| Node::new(Children::Leaf(vec![]))),
+        }
+    }
+}
+
+impl<T: Summarize> Index<usize> for Rope<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        assert!(i < self.len(), "index out of bounds");
+        self.root.get(i)
+    }
+}
+
+pub struct Iter<'a, T: Summarize> {
+    stack: Vec<std::slice::Iter<'a, Node<T>>>, // remaining siblings on every level
+    leaf: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T: Summarize> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(x) = self.leaf.next() {
+                return Some(x);
+            }
+            let Some(node) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            match &node.children {
+                Children::Leaf(items) => self.leaf = items.iter(),
+                Children::Internal(nodes) => self.stack.push(nodes.iter()),
+            }
+        }
+    }
+}
+
+impl<'a, T: Summarize> IntoIterator for &'a Rope<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Summarize for usize {
+        type Summary = usize;
+
+        fn summarize(&self) -> usize {
+            *self
+        }
+    }
+
+    #[test]
+    fn test_rope() {
+        let mut vec: Vec<usize> = (0..1000).collect();
+        let mut rope = Rope::from(vec.clone());
+        // pseudo-random edits
+        let mut x = 1usize;
+        for _ in 0..5000 {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1) >> 1;
+            let i = x % (vec.len() + 1);
+            if x.is_multiple_of(3) && i < vec.len() {
+                assert_eq!(rope.remove(i), vec.remove(i));
+            } else {
+                rope.insert(i, x % 10);
+                vec.insert(i, x % 10);
+            }
+        }
+        assert_eq!(rope.iter().copied().collect::<Vec<_>>(), vec);
+        assert_eq!(rope.iter_from(700).next(), vec.get(700));
+        assert_eq!(rope[321], vec[321]);
+        assert_eq!(rope.summary_before(500), vec[..500].iter().sum::<usize>());
+        let total: usize = vec.iter().sum();
+        let i = rope.search(|s| *s > total / 2);
+        assert!(vec[..i].iter().sum::<usize>() <= total / 2);
+        assert!(vec[..=i].iter().sum::<usize>() > total / 2);
+
+        rope.with_slice(10..20, |s| s.iter_mut().for_each(|x| *x = 100));
+        rope.remove_range(0..10);
+        assert_eq!(rope.iter().take(10).sum::<usize>(), 1000);
+        assert_eq!(rope.summary_before(10), 1000);
+        rope.remove_range(0..rope.len());
+        assert_eq!(rope.len(), 0);
+        assert_eq!(rope.iter().next(), None);
+    }
+}
@@ -0,0 +1,298 @@
+// Terminal frontend, built with crossterm. Virtual whitespace is displayed with a different
+// background and the cursor is displayed as an area like in the web frontend.
+use std::io::{self, Write};
+use std::panic;
+use std::path::PathBuf;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+use crate::content::{Content, CopyMode, FormatCache, GetString, SpanKind};
+use crate::format::Formatter;
+use crate::keys::{handle_key, Key, KeyAction, KeyPress};
+
+const VIRTUAL_BG: Color = Color::AnsiValue(236);
+const AREA_BG: Color = Color::AnsiValue(24);
+const SELECTION_BG: Color = Color::AnsiValue(25);
+
+struct Editor {
+    content: Content,
+    formatter: Box<dyn Formatter>,
+    format_cache: FormatCache,
+    width: usize, // the formatting width, follows the terminal width
+    path: PathBuf,
+    saved_version: u64, // version of the content when the file was last loaded or saved
+    lines: Vec<Vec<(char, bool)>>, // displayed chars of every line and whether they are virtual
+    clipboard: String,
+    scroll: usize, // first displayed line
+    status: String,
+    quit_pending: bool, // ctrl+q was pressed with unsaved changes
+}
+
+// Opens the file at `path` (or an empty buffer if it doesn't exist) and runs the editor until the
+// user quits.
+pub fn run(path: PathBuf, formatter: Box<dyn Formatter>) -> io::Result<()> {
+    let text = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let width = terminal::size()?.0 as usize;
+    let mut editor = Editor::new(path, &text, formatter, width);
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen)?;
+    // restore the terminal before a panic message is printed, it would be lost otherwise
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+    let res = editor.event_loop(&mut stdout);
+    let _ = panic::take_hook();
+    restore_terminal()?;
+    res
+}
+
+fn restore_terminal() -> io::Result<()> {
+    execute!(
+        io::stdout(),
+        terminal::LeaveAlternateScreen,
+        cursor::SetCursorStyle::DefaultUserShape,
+        cursor::Show
+    )?;
+    terminal::disable_raw_mode()
+}
+
+impl Editor {
+    fn new(path: PathBuf, text: &str, formatter: Box<dyn Formatter>, width: usize) -> Editor {
+        let content = Content::from_formatted(text, formatter.as_ref(), width);
+        let mut editor = Editor {
+            saved_version: content.version(),
+            lines: vec![],
+            content,
+            formatter,
+            format_cache: FormatCache::default(),
+            width,
+            path,
+            clipboard: String::new(),
+            scroll: 0,
+            status: "ctrl+s: save, ctrl+q: quit".to_string(),
+            quit_pending: false,
+        };
+        editor.update_lines();
+        editor
+    }
+
+    fn event_loop(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            self.draw(out)?;
+            match event::read()? {
+                Event::Key(e)
+                    if e.kind != KeyEventKind::Release && !self.handle_key_event(e)? =>
+                {
+                    return Ok(());
+                }
+                Event::Resize(width, _) if width as usize != self.width => {
+                    self.width = width as usize;
+                    self.content_changed();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Returns false if the editor should quit
+    fn handle_key_event(&mut self, e: KeyEvent) -> io::Result<bool> {
+        let ctrl = e.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = e.modifiers.contains(KeyModifiers::SHIFT);
+        let alt = e.modifiers.contains(KeyModifiers::ALT);
+        let quit_pending = std::mem::take(&mut self.quit_pending);
+
+        let key = match e.code {
+            KeyCode::Char('q') if ctrl => {
+                if self.is_dirty() && !quit_pending {
+                    self.status = "Unsaved changes, press ctrl+q again to quit".to_string();
+                    self.quit_pending = true;
+                    return Ok(true);
+                }
+                return Ok(false);
+            }
+            KeyCode::Char('s') if ctrl => {
+                std::fs::write(&self.path, self.content.get_string())?;
+                self.saved_version = self.content.version();
+                self.status = format!("Saved {}", self.path.display());
+                return Ok(true);
+            }
+            KeyCode::Char('c') if ctrl => {
+                if let Some(text) = self.content.selected_text(CopyMode::Visible) {
+                    self.clipboard = text;
+                }
+                return Ok(true);
+            }
+            KeyCode::Char('x') if ctrl => {
+                if let Some(text) = self.content.cut(CopyMode::Visible) {
+                    self.clipboard = text;
+                    self.content_changed();
+                }
+                return Ok(true);
+            }
+            KeyCode::Char('v') if ctrl => {
+                self.content
+                    .paste(&self.clipboard, self.formatter.as_ref(), self.width);
+                self.content_changed();
+                return Ok(true);
+            }
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Char(c) => Key::Char(c),
+            _ => return Ok(true),
+        };
+
+        let press = KeyPress {
+            key,
+            ctrl,
+            shift,
+            alt,
+        };
+        if handle_key(&mut self.content, press) == KeyAction::ContentChanged {
+            self.content_changed();
+        }
+        Ok(true)
+    }
+
+    fn content_changed(&mut self) {
+        self.status = self.content.update_virtual_whitespace_cached(
+            self.formatter.as_ref(),
+            self.width,
+            &mut self.format_cache,
+        );
+        self.update_lines();
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.content.version() != self.saved_version
+    }
+
+    // the lines are only computed when the content changes, not on every redraw
+    fn update_lines(&mut self) {
+        self.lines = self
+            .content
+            .lines()
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .flat_map(|span| {
+                        let is_virtual = span.kind == SpanKind::VirtualWhitespace;
+                        span.text
+                            .trim_end_matches('\n')
+                            .chars()
+                            .map(move |c| (c, is_virtual))
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        // at least one text row, the status line is cut off in a terminal with a single row
+        let (width, rows) = (width as usize, height.saturating_sub(1).max(1) as usize);
+
+        let ((area_start, area_end), cursor) = self.content.cursor_pos_2();
+        let selection = self.content.selection_range();
+        if cursor.0 < self.scroll {
+            self.scroll = cursor.0;
+        } else if cursor.0 >= self.scroll + rows {
+            self.scroll = cursor.0 + 1 - rows;
+        }
+
+        queue!(out, cursor::Hide)?;
+        for row in 0..rows {
+            let line_idx = self.scroll + row;
+            let line = self.lines.get(line_idx).map(Vec::as_slice).unwrap_or(&[]);
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            let mut run = String::new();
+            let mut run_bg = Color::Reset;
+            for col in 0..width {
+                let pos = (line_idx, col);
+                let (c, is_virtual) = line.get(col).copied().unwrap_or((' ', false));
+                let bg = if selection.is_some_and(|(start, end)| start <= pos && pos < end) {
+                    SELECTION_BG
+                } else if area_start <= pos && pos < area_end {
+                    AREA_BG
+                } else if is_virtual {
+                    VIRTUAL_BG
+                } else {
+                    Color::Reset
+                };
+                if bg != run_bg {
+                    queue!(out, SetBackgroundColor(run_bg), Print(&run))?;
+                    run.clear();
+                    run_bg = bg;
+                }
+                run.push(c);
+            }
+            queue!(out, SetBackgroundColor(run_bg), Print(&run))?;
+        }
+
+        let name = self.path.display().to_string();
+        let dirty = if self.is_dirty() { " *" } else { "" };
+        let mut status = format!(" {}{} | {}", name, dirty, self.status);
+        status = status.chars().take(width).collect();
+        queue!(
+            out,
+            cursor::MoveTo(0, rows as u16),
+            SetBackgroundColor(Color::Reset),
+            SetAttribute(Attribute::Reverse),
+            Print(format!("{:width$}", status, width = width)),
+            SetAttribute(Attribute::Reset),
+            cursor::MoveTo(cursor.1 as u16, (cursor.0 - self.scroll) as u16),
+            cursor::SetCursorStyle::SteadyBar,
+            cursor::Show,
+        )?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Prettyplease;
+
+    fn key(editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+        editor
+            .handle_key_event(KeyEvent::new(code, modifiers))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let mut editor = Editor::new(
+            PathBuf::from("a.rs"),
+            "fn a() {}\n",
+            Box::new(Prettyplease),
+            80,
+        );
+        key(&mut editor, KeyCode::End, KeyModifiers::SHIFT);
+        key(&mut editor, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert_eq!(editor.clipboard, "fn a() {}");
+        assert!(editor.is_dirty());
+        key(&mut editor, KeyCode::Char('v'), KeyModifiers::CONTROL);
+        assert_eq!(editor.content.get_string(), "fn a() {}\n");
+        // the same text, but after edits that can be undone
+        assert!(editor.is_dirty());
+        key(&mut editor, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        key(&mut editor, KeyCode::Char('z'), KeyModifiers::CONTROL);
+        assert!(!editor.is_dirty());
+    }
+}
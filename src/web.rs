@@ -0,0 +1,660 @@
+// The browser frontend, built with Yew
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use gloo::events::{EventListener, EventListenerOptions};
+use gloo::file::{callbacks::FileReader, Blob, FileReadError, ObjectUrl};
+use gloo::timers::callback::Timeout;
+use gloo::worker::{Spawnable, WorkerBridge};
+use wasm_bindgen::JsCast;
+use web_sys::ClipboardEvent;
+use yew::{html, prelude::*, Component, Html};
+
+use crate::content::{visible_whitespace, Content, CopyMode, CursorPos, GetString, Span, SpanKind};
+use crate::format::{Formatter, Prettyplease};
+use crate::keys::{handle_key, AutoUpdate, Key, KeyAction, KeyPress};
+use crate::language::{self, Language};
+use crate::lexer::Highlight;
+
+mod worker;
+pub use worker::FormatWorker;
+use worker::{FormatRequest, FormatResponse};
+
+const TEXT_SIZE: usize = 12;
+// the script of the format worker, built by trunk from the `token_editor_worker` binary
+const WORKER_PATH: &str = "./token_editor_worker.js";
+
+pub struct Model {
+    lines: Vec<Vec<Span>>, // the displayed text
+    //cursor: CursorPos,
+    cursor2: ((usize, usize), (usize, usize)),
+    cursor_small: (usize, usize),
+    selection: Option<CursorPos>,
+    content: Content,
+    language: &'static Language,
+    formatter: Box<dyn Formatter>,
+    worker: WorkerBridge<FormatWorker>,
+    format_generation: u64, // incremented on every change that invalidates running formatting
+    format_in_flight: bool, // the worker is formatting
+    format_pending: bool,   // the content has to be sent to the worker once it's done
+    auto_update: AutoUpdate,
+    idle_delay: u32,               // in ms, for `AutoUpdate::Idle`
+    idle_timeout: Option<Timeout>, // restarted on every change
+    link: html::Scope<Model>,
+    update_item_only: bool, // auto updates only reformat the item at the cursor
+    show_whitespace: bool,  // mark typed and virtual whitespace differently
+    window_width: usize,
+    char_dimensions: (f32, f32),
+    text_ref: NodeRef,
+    container_ref: NodeRef,
+    dragging: bool,
+    copy_mode: CopyMode,
+    _clipboard_listeners: Vec<EventListener>,
+    file_name: String,
+    saved_version: u64, // version of the content when the file was last loaded or saved
+    file_reader: Option<FileReader>,
+    download_url: Option<ObjectUrl>,
+    _resize_listener: EventListener,
+}
+
+pub enum Msg {
+    KeyEvt(KeyboardEvent),
+    ClearVirtualWhitespace,
+    Format,
+    SetAutoUpdate(AutoUpdate),
+    SetIdleDelay(u32),
+    Idle,
+    ToggleUpdateScope,
+    ToggleShowWhitespace,
+    MouseDown(MouseEvent),
+    MouseMove(MouseEvent),
+    MouseUp,
+    Copy(ClipboardEvent),
+    Cut(ClipboardEvent),
+    Paste(ClipboardEvent),
+    ToggleCopyMode,
+    OpenFile(Option<web_sys::File>),
+    FileLoaded(String, Result<String, FileReadError>),
+    Save(CopyMode),
+    UpdateWidth(usize),
+    Formatted(FormatResponse),
+}
+
+impl Model {
+    // renders an area spanning from `start` to `end` (e.g. the cursor or a selection)
+    fn area_html(&self, (start, end): CursorPos, class: Classes) -> Html {
+        let (w, h) = self.char_dimensions;
+
+        let width_first_line = if start.0 == end.0 {
+            w * (end.1 - start.1) as f32
+        } else {
+            // to the window edge, or the end of the line if it's longer
+            let line_len: usize = self.lines[start.0]
+                .iter()
+                .map(|span| span.text.trim_end_matches('\n').chars().count())
+                .sum();
+            w * self.window_width.max(line_len).saturating_sub(start.1) as f32
+        };
+        let first_line_style = format!(
+            "top: {}px; left: {}px; width: {}px; height: {}px;",
+            h * start.0 as f32,
+            w * start.1 as f32 - 1.0,
+            width_first_line,
+            h
+        );
+        let num_mid_lines = (end.0 - start.0).saturating_sub(1);
+        let mid_lines_style = format!(
+            "top: {}px; left: -1px; width: {}px; height: {}px;",
+            h * (start.0 + 1) as f32,
+            w * self.window_width as f32,
+            h * num_mid_lines as f32
+        );
+        let last_line_style = format!(
+            "top: {}px; left: -1px; width: {}px; height: {}px;",
+            h * end.0 as f32,
+            w * end.1 as f32,
+            h
+        );
+
+        html! {
+            <>
+                <div class={class.clone()} style={first_line_style}></div>
+                if num_mid_lines > 0 {
+                    <div class={class.clone()} style={mid_lines_style}></div>
+                }
+                if start.0 != end.0 {
+                    <div class={class} style={last_line_style}></div>
+                }
+            </>
+        }
+    }
+
+    // `press` is the key press that changed the content, if any
+    fn content_changed(&mut self, press: Option<KeyPress>) {
+        self.format_generation += 1;
+        if self.auto_update.updates_after(press) {
+            self.auto_update_virtual_whitespace();
+        }
+        if self.auto_update == AutoUpdate::Idle {
+            // replacing the timeout cancels the previous one
+            let link = self.link.clone();
+            self.idle_timeout = Some(Timeout::new(self.idle_delay, move || {
+                link.send_message(Msg::Idle)
+            }));
+        }
+        self.update_cursor();
+        self.lines = self.content.lines();
+    }
+
+    fn auto_update_virtual_whitespace(&mut self) {
+        if self.update_item_only {
+            // a running worker update would overwrite this one
+            self.format_generation += 1;
+            let res = self
+                .content
+                .update_virtual_whitespace_at_cursor(self.formatter.as_ref(), self.window_width);
+            web_sys::console::log_1(&res.into());
+        } else {
+            self.update_virtual_whitespace();
+        }
+    }
+
+    // Formats the content in the worker. The result is applied when it arrives, unless the content
+    // changed in the meantime.
+    fn update_virtual_whitespace(&mut self) {
+        self.format_generation += 1;
+        if self.format_in_flight {
+            // only the latest content is sent once the worker is done
+            self.format_pending = true;
+        } else {
+            self.send_format_request();
+        }
+    }
+
+    fn send_format_request(&mut self) {
+        self.format_in_flight = true;
+        self.worker.send(FormatRequest {
+            generation: self.format_generation,
+            typed: self.content.typed_string(),
+            language: self.language.name.to_string(),
+            max_width: self.window_width,
+        });
+    }
+
+    // visual position (line, column) of a mouse event
+    fn mouse_pos(&self, e: &MouseEvent) -> (usize, usize) {
+        let rect = self
+            .text_ref
+            .cast::<web_sys::Element>()
+            .unwrap()
+            .get_bounding_client_rect();
+        let (w, h) = self.char_dimensions;
+        let x = (e.client_x() as f64 - rect.left()) as f32;
+        let y = (e.client_y() as f64 - rect.top()) as f32;
+        ((y / h).max(0.0) as usize, (x / w).round().max(0.0) as usize)
+    }
+
+    fn has_focus(&self) -> bool {
+        let active = gloo::utils::document().active_element();
+        active.is_some() && active == self.container_ref.cast::<web_sys::Element>()
+    }
+
+    // The displayed text. If whitespace is shown, typed whitespace is displayed as `·` and `¶` and
+    // virtual whitespace gets a tinted background.
+    fn text_html(&self) -> Html {
+        let span_html = |span: &Span| {
+            let class = match span.kind {
+                SpanKind::Token => "token",
+                SpanKind::TypedWhitespace => "typed-ws",
+                SpanKind::VirtualWhitespace => "virtual-ws",
+            };
+            let text = if self.show_whitespace && span.kind == SpanKind::TypedWhitespace {
+                visible_whitespace(&span.text)
+            } else {
+                span.text.clone()
+            };
+            let highlight = span.highlight.map(|h| match h {
+                Highlight::Keyword => "keyword",
+                Highlight::Literal => "literal",
+                Highlight::Lifetime => "lifetime",
+                Highlight::Comment => "comment",
+                Highlight::Macro => "macro",
+            });
+            let class = classes!(class, highlight);
+            html! { <span {class} data-elmt={span.elmt.to_string()}>{ text }</span> }
+        };
+        let class = classes!(self.show_whitespace.then_some("show-whitespace"));
+        html! { <pre {class}>{ for self.lines.iter().flatten().map(span_html) }</pre> }
+    }
+
+    fn update_cursor(&mut self) {
+        let (cursor2, cursor_small) = self.content.cursor_pos_2();
+        self.cursor2 = cursor2;
+        self.cursor_small = cursor_small;
+        self.selection = self.content.selection_range();
+    }
+}
+
+// number of chars that fit into the window next to the page padding
+fn editor_width(char_width: f32) -> usize {
+    let window_width = gloo::utils::window()
+        .inner_width()
+        .ok()
+        .and_then(|w| w.as_f64())
+        .unwrap_or(0.0);
+    let chars = ((window_width - 44.0) / char_width as f64).max(0.0) as usize;
+    chars.clamp(40, 150)
+}
+
+impl Component for Model {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        //let typed = "fn test(&self,  other:\n  \n&mut usize){let x=(self+1)*other;\n return1<y}";
+        //let typed = "fn test(other:&mut usize){let array=[1123456, 531432124, 43241432, 4312432, 9432, 432,4328,432];let x=(self+1)*other;return 1<y}";
+        let _visible =
+            "fn test(other: &mut usize) {\n    let x = (self + 1) * other;\n    return 1 < y\n}";
+        let typed = "fn test(){let x=1+2-3;}";
+        let language = language::RUST;
+        let formatter = Box::new(Prettyplease);
+        let mut content = Content::from_strings(typed, typed);
+
+        let document = web_sys::window().unwrap().document().unwrap();
+
+        let elmt = document.create_element("span").unwrap();
+        let text = document.create_text_node("x");
+        elmt.append_child(&text).unwrap();
+        elmt.set_attribute("style", &format!("font-family: monospace; position: absolute; top: -1000px; left: -1000px; font-size: {}pt;", TEXT_SIZE)).unwrap();
+        document.body().unwrap().append_child(&elmt).unwrap();
+        let rect = elmt.get_bounding_client_rect();
+        let char_dimensions = (rect.width() as f32, rect.height() as f32);
+        web_sys::console::log_1(&format!("{}, {}", char_dimensions.0, char_dimensions.1).into());
+
+        // the formatting width follows the width of the window
+        let window_width = editor_width(char_dimensions.0);
+        content.update_virtual_whitespace(formatter.as_ref(), window_width);
+        let link = ctx.link().clone();
+        let resize_listener = EventListener::new(&gloo::utils::window(), "resize", move |_| {
+            link.send_message(Msg::UpdateWidth(editor_width(char_dimensions.0)))
+        });
+        let link = ctx.link().clone();
+        let worker = FormatWorker::spawner()
+            .callback(move |res| link.send_message(Msg::Formatted(res)))
+            .spawn(WORKER_PATH);
+
+        // Clipboard events are dispatched to the body if the focused element isn't editable, so
+        // they are handled on the document level.
+        let clipboard_listeners = [
+            ("copy", Msg::Copy as fn(ClipboardEvent) -> Msg),
+            ("cut", Msg::Cut),
+            ("paste", Msg::Paste),
+        ]
+        .iter()
+        .map(|&(event, msg)| {
+            let link = ctx.link().clone();
+            EventListener::new_with_options(
+                &document,
+                event,
+                EventListenerOptions::enable_prevent_default(),
+                move |e| {
+                    if let Some(e) = e.dyn_ref::<ClipboardEvent>() {
+                        link.send_message(msg(e.clone()));
+                    }
+                },
+            )
+        })
+        .collect();
+
+        let saved_version = content.version();
+        let mut model = Model {
+            lines: content.lines(),
+            //cursor: content.cursor_pos(),
+            cursor2: ((0, 11), (2, 4)),
+            cursor_small: (0, 0),
+            selection: None,
+            content,
+            language,
+            formatter,
+            worker,
+            format_generation: 0,
+            format_in_flight: false,
+            format_pending: false,
+            auto_update: AutoUpdate::Always,
+            idle_delay: 500,
+            idle_timeout: None,
+            link: ctx.link().clone(),
+            update_item_only: false,
+            show_whitespace: false,
+            window_width,
+            char_dimensions,
+            text_ref: NodeRef::default(),
+            container_ref: NodeRef::default(),
+            dragging: false,
+            copy_mode: CopyMode::Visible,
+            _clipboard_listeners: clipboard_listeners,
+            file_name: "untitled.rs".to_string(),
+            saved_version,
+            file_reader: None,
+            download_url: None,
+            _resize_listener: resize_listener,
+        };
+        model.update_cursor();
+        model
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::KeyEvt(e) => {
+                e.stop_propagation();
+                // cmd on macOS
+                let ctrl = e.ctrl_key() || e.meta_key();
+                if ctrl && matches!(e.key().as_ref(), "c" | "x" | "v") {
+                    // let the browser dispatch the clipboard events
+                    return false;
+                }
+                e.prevent_default();
+                let key = match e.key().as_ref() {
+                    "ArrowLeft" => Key::Left,
+                    "ArrowRight" => Key::Right,
+                    "ArrowDown" => Key::Down,
+                    "ArrowUp" => Key::Up,
+                    "End" => Key::End,
+                    "Home" => Key::Home,
+                    "Backspace" => Key::Backspace,
+                    "Delete" => Key::Delete,
+                    "Enter" => Key::Enter,
+                    x if x.len() == 1 => Key::Char(x.chars().next().unwrap()),
+                    _ => return true,
+                };
+                let press = KeyPress {
+                    key,
+                    ctrl,
+                    shift: e.shift_key(),
+                    alt: e.alt_key(),
+                };
+                match handle_key(&mut self.content, press) {
+                    KeyAction::CursorMoved => self.update_cursor(),
+                    KeyAction::ContentChanged => self.content_changed(Some(press)),
+                    KeyAction::None => (),
+                }
+                web_sys::console::log_1(&format!("{:?}", e.key()).into());
+                // FIXME: implement
+            }
+            Msg::ClearVirtualWhitespace => {
+                self.format_generation += 1;
+                self.content.clear_virtual_whitespace();
+                self.update_cursor();
+                self.lines = self.content.lines();
+            }
+            Msg::Format => {
+                // the worker's result is applied in `Msg::Formatted`
+                self.update_virtual_whitespace();
+                return false;
+            }
+            Msg::SetAutoUpdate(mode) => {
+                self.auto_update = mode;
+                self.idle_timeout = None;
+            }
+            Msg::SetIdleDelay(delay) => {
+                self.idle_delay = delay;
+                return false;
+            }
+            Msg::Idle => {
+                self.idle_timeout = None;
+                self.auto_update_virtual_whitespace();
+                self.update_cursor();
+                self.lines = self.content.lines();
+            }
+            Msg::ToggleUpdateScope => {
+                self.update_item_only = !self.update_item_only;
+            }
+            Msg::ToggleShowWhitespace => {
+                self.show_whitespace = !self.show_whitespace;
+            }
+            Msg::MouseDown(e) => {
+                if e.button() != 0 {
+                    return false;
+                }
+                self.content.set_selecting(e.shift_key());
+                self.content.cursor_to(self.mouse_pos(&e));
+                self.dragging = true;
+                self.update_cursor();
+            }
+            Msg::MouseMove(e) => {
+                if !self.dragging {
+                    return false;
+                }
+                if e.buttons() & 1 == 0 {
+                    // the button was released outside of the editor
+                    self.dragging = false;
+                    return false;
+                }
+                self.content.set_selecting(true);
+                self.content.cursor_to(self.mouse_pos(&e));
+                self.update_cursor();
+            }
+            Msg::MouseUp => {
+                self.dragging = false;
+                return false;
+            }
+            Msg::Copy(e) => {
+                if !self.has_focus() {
+                    return false;
+                }
+                if let (Some(text), Some(data)) = (
+                    self.content.selected_text(self.copy_mode),
+                    e.clipboard_data(),
+                ) {
+                    data.set_data("text/plain", &text).unwrap();
+                }
+                e.prevent_default();
+                return false;
+            }
+            Msg::Cut(e) => {
+                if !self.has_focus() {
+                    return false;
+                }
+                if let (Some(text), Some(data)) =
+                    (self.content.cut(self.copy_mode), e.clipboard_data())
+                {
+                    data.set_data("text/plain", &text).unwrap();
+                }
+                e.prevent_default();
+                self.content_changed(None);
+            }
+            Msg::Paste(e) => {
+                if !self.has_focus() {
+                    return false;
+                }
+                if let Some(text) = e
+                    .clipboard_data()
+                    .and_then(|d| d.get_data("text/plain").ok())
+                {
+                    self.content
+                        .paste(&text, self.formatter.as_ref(), self.window_width);
+                }
+                e.prevent_default();
+                self.content_changed(None);
+            }
+            Msg::OpenFile(file) => {
+                let Some(file) = file else {
+                    return false;
+                };
+                let file = gloo::file::File::from(file);
+                let name = file.name();
+                let link = ctx.link().clone();
+                self.file_reader = Some(gloo::file::callbacks::read_as_text(&file, move |res| {
+                    link.send_message(Msg::FileLoaded(name, res))
+                }));
+                return false;
+            }
+            Msg::FileLoaded(name, res) => {
+                self.file_reader = None;
+                let text = match res {
+                    Ok(text) => text,
+                    Err(e) => {
+                        web_sys::console::log_1(&format!("Reading {} failed: {}", name, e).into());
+                        return false;
+                    }
+                };
+                self.language = language::for_path(&name);
+                self.formatter = (self.language.formatter)();
+                self.content =
+                    Content::from_formatted(&text, self.formatter.as_ref(), self.window_width);
+                self.file_name = name;
+                self.saved_version = self.content.version();
+                self.content_changed(None);
+            }
+            Msg::Save(mode) => {
+                let text = match mode {
+                    CopyMode::Visible => self.content.get_string(),
+                    CopyMode::Typed => self.content.typed_string(),
+                };
+                let url =
+                    ObjectUrl::from(Blob::new_with_options(text.as_str(), Some("text/plain")));
+                let a = gloo::utils::document().create_element("a").unwrap();
+                a.set_attribute("href", &url).unwrap();
+                a.set_attribute("download", &self.file_name).unwrap();
+                a.unchecked_into::<web_sys::HtmlElement>().click();
+                // the url is revoked when it's dropped, so keep it alive until the next download
+                self.download_url = Some(url);
+                self.saved_version = self.content.version();
+            }
+            Msg::ToggleCopyMode => {
+                self.copy_mode = match self.copy_mode {
+                    CopyMode::Visible => CopyMode::Typed,
+                    CopyMode::Typed => CopyMode::Visible,
+                };
+            }
+            Msg::Formatted(res) => {
+                self.format_in_flight = false;
+                if self.format_pending {
+                    self.format_pending = false;
+                    self.send_format_request();
+                }
+                // the result is stale if the content or the width changed since the request
+                if res.generation != self.format_generation {
+                    return false;
+                }
+                web_sys::console::log_1(&res.message.into());
+                if !self.content.set_virtual_whitespace(&res.virtual_whitespace) {
+                    return false;
+                }
+                self.update_cursor();
+                self.lines = self.content.lines();
+            }
+            Msg::UpdateWidth(n) => {
+                if n == self.window_width {
+                    return false;
+                }
+                self.window_width = n;
+                self.update_virtual_whitespace();
+            }
+        }
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        static TICK: AtomicBool = AtomicBool::new(false);
+
+        // toggle TICK
+        let tick = !TICK.load(Ordering::SeqCst);
+        TICK.store(tick, Ordering::SeqCst);
+
+        // switching between two different classes that are equivalent here to force the animation to
+        // start again whenever view is called. The effect is that the cursor immediately becomes visible
+        // when moving around
+        let blink_class = if tick { "blink_me" } else { "blink_me2" };
+
+        let (w, h) = self.char_dimensions;
+
+        let x = (self.cursor2.0).1 as f32 * w;
+        let y = (self.cursor2.0).0 as f32 * h;
+        let s = format!(
+            "background-color: #7799bb; position: absolute; width: 2px; height: {}px; top: {}px; left: {}px;", 
+            h,
+            y,
+            x as i32 - 1,
+        );
+        let s_small = format!(
+            "background-color: #7799bb; position: absolute; width: 2px; height: {}px; top: {}px; left: {}px;", 
+            h,
+            h*self.cursor_small.0 as f32,
+            w * self.cursor_small.1 as f32 - 1.0,
+        );
+
+        let div_style = format!(
+            "font-family: monospace; position: relative; font-size: {}pt; width: {}ch;",
+            TEXT_SIZE, self.window_width
+        );
+        let accept = language::LANGUAGES
+            .iter()
+            .flat_map(|l| l.extensions)
+            .map(|e| format!(".{}", e))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        html! {
+            <div style="background-color: #eee; padding: 20px; height: 100%; box-sizing: border-box;">
+                <nav class="menu">
+                    <span class="file-name">{ if self.content.version() != self.saved_version { format!("{} *", self.file_name) } else { self.file_name.clone() } }</span>
+                    <input type="file" {accept} onchange={ctx.link().callback(|e: Event| {
+                        let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+                        Msg::OpenFile(input.files().and_then(|files| files.get(0)))
+                    })} />
+                    <button onclick={ctx.link().callback(|_| Msg::Save(CopyMode::Visible))}>{ "Save formatted" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::Save(CopyMode::Typed))}>{ "Save typed" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::ClearVirtualWhitespace)}>{ "Clear virtual whitespace" }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::Format)}>{ "Update virtual whitespace" }</button>
+                    <label>{ "Auto update: " }
+                        <select onchange={ctx.link().callback(|e: Event| {
+                            let select: web_sys::HtmlSelectElement = e.target_unchecked_into();
+                            Msg::SetAutoUpdate(AutoUpdate::ALL[select.selected_index().max(0) as usize])
+                        })}>
+                            { for AutoUpdate::ALL.iter().map(|mode| html! {
+                                <option selected={*mode == self.auto_update}>{ mode.name() }</option>
+                            }) }
+                        </select>
+                    </label>
+                    if self.auto_update == AutoUpdate::Idle {
+                        <input type="number" min="0" step="100" title="Idle delay (ms)" value={self.idle_delay.to_string()} onchange={ctx.link().callback(|e: Event| {
+                            let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+                            Msg::SetIdleDelay(input.value().parse().unwrap_or(500))
+                        })} />
+                    }
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleUpdateScope)}>{ if self.update_item_only {"Update: current item"} else {"Update: whole file"} }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleShowWhitespace)}>{ if self.show_whitespace {"Hide whitespace"} else {"Show whitespace"} }</button>
+                    <button onclick={ctx.link().callback(|_| Msg::ToggleCopyMode)}>{ if self.copy_mode == CopyMode::Visible {"Copy formatted text"} else {"Copy typed text"} }</button>
+                    <input oninput={ctx.link().callback(|e: InputEvent| {
+                        let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+                        Msg::UpdateWidth(input.value().parse().unwrap())
+                    })} type="range" min="40" max="150" value={self.window_width.to_string()} class="slider" style="width:500px" />
+                </nav>
+                <div class="container" style="width: fit-content; padding: 1px; background-color: white;" onkeydown={ctx.link().callback(Msg::KeyEvt)} tabindex="0" ref={self.container_ref.clone()}
+                    onmousedown={ctx.link().callback(Msg::MouseDown)}
+                    onmousemove={ctx.link().callback(Msg::MouseMove)}
+                    onmouseup={ctx.link().callback(|_| Msg::MouseUp)}
+                    ondragover={Callback::from(|e: DragEvent| e.prevent_default())}
+                    ondrop={ctx.link().callback(|e: DragEvent| {
+                        e.prevent_default();
+                        Msg::OpenFile(e.data_transfer().and_then(|d| d.files()).and_then(|files| files.get(0)))
+                    })}>
+                    <div style={div_style} ref={self.text_ref.clone()}>
+                        { self.text_html() }
+
+                        if let Some(selection) = self.selection {
+                            { self.area_html(selection, classes!("selection")) }
+                        }
+                        if self.cursor2.0 == self.cursor2.1 {
+                            <div id="cursor" class={blink_class} style={s}></div>
+                        } else {
+                            { self.area_html(self.cursor2, classes!("area", blink_class)) }
+                            if (self.cursor2.0).0 != (self.cursor2.1).0 { <div id="cursor_small" class={blink_class} style={s_small}></div> }
+                        }
+                        // <pre>{ format!("{}|", " ".repeat(self.window_width)) }</pre>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Formatting in a Web Worker, so that formatting large files doesn't block the input. The worker
+// gets the typed text and returns the virtual whitespace of every element, which the frontend
+// applies if the text hasn't changed in the meantime.
+use gloo::worker::{HandlerId, Worker, WorkerScope};
+use serde::{Deserialize, Serialize};
+
+use crate::content::{Content, FormatCache};
+use crate::language;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FormatRequest {
+    pub generation: u64, // identifies the version of the typed text
+    pub typed: String,
+    pub language: String,
+    pub max_width: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FormatResponse {
+    pub generation: u64,
+    pub virtual_whitespace: Vec<(usize, usize)>,
+    pub message: String,
+}
+
+pub struct FormatWorker {
+    cache: FormatCache,
+    language: String, // of the cached items
+}
+
+impl Worker for FormatWorker {
+    type Message = ();
+    type Input = FormatRequest;
+    type Output = FormatResponse;
+
+    fn create(_scope: &WorkerScope<Self>) -> Self {
+        FormatWorker {
+            cache: FormatCache::default(),
+            language: String::new(),
+        }
+    }
+
+    fn update(&mut self, _scope: &WorkerScope<Self>, _msg: ()) {}
+
+    fn received(&mut self, scope: &WorkerScope<Self>, req: FormatRequest, id: HandlerId) {
+        if req.language != self.language {
+            self.cache = FormatCache::default();
+            self.language = req.language.clone();
+        }
+        let language = language::by_name(&req.language).unwrap_or(language::RUST);
+        let formatter = (language.formatter)();
+        let mut content = Content::from_string(&req.typed);
+        let message = content.update_virtual_whitespace_cached(
+            formatter.as_ref(),
+            req.max_width,
+            &mut self.cache,
+        );
+        scope.respond(
+            id,
+            FormatResponse {
+                generation: req.generation,
+                virtual_whitespace: content.virtual_whitespace(),
+                message,
+            },
+        );
+    }
+}